@@ -7,7 +7,7 @@ use crate::hal::spi::Spi;
 use cortex_m::asm::delay;
 use mocca_matrix::color;
 use mocca_matrix::prelude::*;
-use mocca_matrix::{hex, hex::prelude::*, math::Vec2};
+use mocca_matrix::{fixed, hex, hex::prelude::*, math::Vec2};
 #[macro_use]
 extern crate cortex_m_rt as rt;
 use rt::ExceptionFrame;
@@ -15,7 +15,6 @@ use smart_leds::{brightness, SmartLedsWrite, RGB8};
 use ws2812::Ws2812;
 use ws2812_spi as ws2812;
 extern crate panic_semihosting;
-use micromath::F32Ext;
 
 #[entry]
 fn main() -> ! {
@@ -94,14 +93,12 @@ fn main() -> ! {
                 canvas.1.iter_mut().for_each(|v| {
                     *v = brightness(core::iter::once(*v), 210).next().unwrap();
                 });
-                let f = ((i * 6) as f32).to_radians();
-                let s = f.sin();
-                let c = f.cos();
-                // let (sin, cos) = f.sin();
-                // f.sin()
-                // let v0 = Vec2::new((s * -5f32) as i32, (c * -5f32) as i32);
+                let f = (i * 6 * 65536 / 360) as u16;
+                let s = fixed::sin16(f) as i32;
+                let c = fixed::cos16(f) as i32;
+                // let v0 = Vec2::new(s * -5 / 32767, c * -5 / 32767);
                 let v0 = Cube::zero();
-                let v = Vec2::new((s * 15f32) as i32, (c * 15f32) as i32);
+                let v = Vec2::new(s * 15 / 32767, c * 15 / 32767);
 
                 canvas.line(v0.into(), v.into(), color::GREEN);
 
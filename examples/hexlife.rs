@@ -1,49 +1,38 @@
 #![no_main]
 #![no_std]
-#![feature(array_value_iter)]
-#![feature(slice_fill)]
 
-use core::convert::Infallible;
-
-use hal::gpio::{Input, PullUp};
-use mocca_matrix::{bitzet::Bitzet, prelude::*};
-use stm32l4xx_hal as hal;
-use ws2812_spi as ws2812;
-#[macro_use]
-extern crate cortex_m_rt as rt;
-use crate::hal::delay::Delay;
 use crate::hal::prelude::*;
 use crate::hal::spi::Spi;
-use crate::hal::stm32;
-use crate::rt::entry;
-use crate::rt::ExceptionFrame;
-use crate::ws2812::Ws2812;
-use cortex_m::peripheral::Peripherals;
+use mocca_matrix::{
+    automaton::{self, Automaton, Topology},
+    color::Palette,
+    effects::{self, life::Action, Effect, FRAME_MS},
+    prelude::*,
+};
+#[macro_use]
+extern crate cortex_m_rt as rt;
+use rt::ExceptionFrame;
 use smart_leds::{brightness, SmartLedsWrite, RGB8};
-extern crate cortex_m_semihosting as sh;
+use ws2812::Ws2812;
+use ws2812_spi as ws2812;
 extern crate panic_semihosting;
-use bitset_core::BitSet;
-use mocca_matrix::math::Vec2;
-#[entry]
-fn main() -> ! {
-    if let (Some(p), Some(cp)) = (stm32::Peripherals::take(), Peripherals::take()) {
-        // Constrain clocking registers
-        let mut flash = p.FLASH.constrain();
-        let mut rcc = p.RCC.constrain();
-        let mut pwr = p.PWR.constrain(&mut rcc.apb1r1);
-        let clocks = rcc // full speed (64 & 80MHz) use the 16MHZ HSI osc + PLL (but slower / intermediate values need MSI)
-            .cfgr
-            .sysclk(80.mhz())
-            .pclk1(80.mhz())
-            .pclk2(80.mhz())
-            .freeze(&mut flash.acr, &mut pwr);
 
-        let mut gpioa = p.GPIOA.split(&mut rcc.ahb2);
+// Hex game of life seeded with the tiles of Advent of Code 2020 day 24, reseeded randomly when it
+// gets stuck. Hold the user button to pause.
 
-        // Get delay provider
-        let mut delay = Delay::new(cp.SYST, clocks);
+/// birth / survival rule
+const RULE: &str = "B2/S12";
+/// wrap around at the largest hexagon that is complete on the panel, tiles of the puzzle input
+/// beyond it are wrapped onto it
+const TOPOLOGY: Topology = Topology::Torus(6);
+/// duration of one generation
+const STEP_MS: u32 = 250;
 
-        // Configure pins for SPI
+#[entry]
+fn main() -> ! {
+    if let Some(mut periphery) = setup() {
+        let mut gpioa = periphery.gpioa;
+        let mut gpioc = periphery.gpioc;
         let (sck, miso, mosi) = cortex_m::interrupt::free(move |cs| {
             (
                 gpioa.pa5.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
@@ -54,208 +43,43 @@ fn main() -> ! {
 
         // Configure SPI with 3Mhz rate
         let spi = Spi::spi1(
-            p.SPI1,
+            periphery.spi1,
             (sck, miso, mosi),
             ws2812::MODE,
             3_000_000.hz(),
-            clocks,
-            &mut rcc.apb2,
+            periphery.clocks,
+            &mut periphery.apb2,
         );
-        let mut gpioc = p.GPIOC.split(&mut rcc.ahb2);
+        let mut ws = Ws2812::new(spi);
         let button = gpioc
             .pc13
             .into_pull_up_input(&mut gpioc.moder, &mut gpioc.pupdr);
-        let mut ws = Ws2812::new(spi);
-        let mut data = [RGB8::new(0, 0, 0); NUM_LEDS];
-        let mut rainbow = Rainbow::step(13);
-        for _ in 0..1 {
-            effects::kitt(&mut ws, &mut rainbow, &mut data);
-        }
-        delay.delay_ms(200u8);
-        ws.write(brightness(data.iter().cloned(), 0)).unwrap();
-        // button_wait_debounced(&button, &mut delay);
-        run(&mut ws, &mut delay, &button);
-        let mut data = [RGB8::new(255, 0, 0); NUM_LEDS];
-        ws.write(brightness(data.iter().cloned(), 32)).unwrap();
-    }
-    unreachable!();
-}
-
-fn adjacent(v: Vec2) -> [Vec2; 6] {
-    let xshift = v.y.abs() % 2;
-    let mut d = [
-        Vec2::new(1, 0),
-        Vec2::new(-1, 0),
-        Vec2::new(-1 + xshift, 1),
-        Vec2::new(0 + xshift, 1),
-        Vec2::new(-1 + xshift, -1),
-        Vec2::new(0 + xshift, -1),
-    ];
 
-    d.iter_mut().for_each(|f| *f = *f + v);
-    d
-}
-#[test]
-fn test_hex() {
-    println!(
-        "{:?}",
-        std::array::IntoIter::new(adjacent(Vec2(0, 0))).collect::<Vec<_>>()
-    );
-    println!("{:?}", adjacent(Vec2(0, 1)));
-    println!("{:?}", adjacent(Vec2(0, -1)));
-}
-fn run<WS: SmartLedsWrite<Color = RGB8, Error = hal::spi::Error>>(
-    ws: &mut WS,
-    delay: &mut Delay,
-    button: &dyn InputPin<Error = Infallible>,
-) -> Result<(), mocca_matrix::Error> {
-    type BitzetN = Bitzet<128>;
-    let mut data = [RGB8::default(); NUM_LEDS];
-    let mut black = BitzetN::new();
-    for (i, line) in input().iter().enumerate() {
-        let mut c = line.chars();
-        let mut x = 0i32;
-        let mut y = 0i32;
-
-        // data[i % NUM_LEDS] = RGB8::new(0, 255, 0);
-        let mut prev = None;
-        fn reset_prev(prev: Option<(i16, RGB8)>, data: &mut [RGB8]) {
-            if let Some((led, color)) = prev {
-                data[led as usize] = color;
-            }
+        let mut automaton = Automaton::<128>::new(automaton::Rule::parse(RULE).unwrap());
+        automaton.topology = TOPOLOGY;
+        for path in input().iter() {
+            automaton.toggle(automaton::walk(path));
         }
-        loop {
-            match c.next() {
-                Some('e') => x += 1,
-                Some('w') => x -= 1,
-                Some('s') => match c.next() {
-                    Some('e') => {
-                        x += (y.abs() % 2);
-                        y += 1
-                    }
-                    Some('w') => {
-                        y += 1;
-                        x -= (y.abs() % 2);
-                    }
-                    _ => break,
-                },
-                Some('n') => match c.next() {
-                    Some('e') => {
-                        x += (y.abs() % 2);
-                        y -= 1
-                    }
-                    Some('w') => {
-                        y -= 1;
-                        x -= y.abs() % 2;
-                    }
-                    _ => break,
-                },
-                None => break,
+        // start over with a random world once the tiles settle down
+        let mut life = effects::Life::new(automaton, Palette::Wheel, STEP_MS, 1)
+            .trail(220)
+            .on_stagnation(Action::Reseed(80), 20);
 
-                _ => break,
+        let mut data = [RGB8::default(); NUM_LEDS];
+        loop {
+            life.render(&mut data);
+            ws.write(brightness(data.iter().cloned(), 16)).unwrap();
+            if !button_pressed(&button) {
+                life.update(FRAME_MS);
             }
-
-            reset_prev(prev, &mut data);
-            prev = get_matrix((x + 10) as usize, (y + 10) as usize, &mut data).ok();
-            set_matrix(
-                (x + 10) as usize,
-                (y + 10) as usize,
-                RGB8::new(0, 255, 0),
-                &mut data,
-            );
-            ws.write(brightness(data.iter().cloned(), 32)).unwrap();
-            delay.delay_ms(8u8);
-        }
-        reset_prev(prev, &mut data);
-
-        if black.contains(&Vec2 { x, y }) {
-            black.remove(&Vec2 { x, y });
-        } else {
-            black.insert(Vec2 { x, y });
-        }
-        set_matrix(
-            (x + 10) as usize,
-            (y + 10) as usize,
-            RGB8::new(0, 0, 255),
-            &mut data,
-        );
-        ws.write(brightness(data.iter().cloned(), 32)).unwrap();
-    }
-    {
-        let mut rainbow = Rainbow::step(3);
-        for _ in 0..100 {
-            let c = rainbow.next().unwrap();
-            data.iter_mut().for_each(|v| {
-                if v.r != 0 || v.g != 0 || v.b != 0 {
-                    *v = c
-                }
-            });
-            ws.write(brightness(data.iter().cloned(), 32)).unwrap();
         }
     }
-    // while button.is_high().unwrap() {}
-
-    let mut i: usize = 0;
-    let mut keep_on = [0u32; NUM_LEDS / 32 + 1];
-    let mut rainbow = Rainbow::step(7);
-    loop {
-        let warp_mode = false; //button.is_low().unwrap();
-        let hold_mode = button.is_low().unwrap();
-        if i % 25 == 0 || warp_mode {
-            let mut black_new = Bitzet::new();
-
-            for v in black.iter() {
-                let n = adjacent(v).iter().filter(|v| black.contains(*v)).count();
-                if (1..=2).contains(&n) {
-                    black_new.insert(v);
-                }
-            }
-
-            let white = black
-                .iter()
-                .flat_map(|v| core::array::IntoIter::new(adjacent(v)))
-                .collect::<BitzetN>();
-
-            let white = white.difference(&black);
-            for v in white.iter() {
-                let n = adjacent(v).iter().filter(|v| black.contains(*v)).count();
-                if n == 2 && v.x.abs() < 15 && v.y.abs() < 15 {
-                    black_new.insert(v);
-                }
-            }
-
-            if !hold_mode {
-                black = black_new;
-            }
-            keep_on.fill(0);
-            if warp_mode {
-                data.fill(RGB8::default());
-            }
-            for v in black.iter() {
-                if let Ok(addr) = set_matrix(
-                    (v.x + 10) as usize,
-                    (v.y + 10) as usize,
-                    rainbow.next().unwrap(),
-                    &mut data,
-                ) {
-                    keep_on.bit_set(addr as usize);
-                }
-            }
-        }
-        i = i.overflowing_add(1).0;
+    unreachable!();
+}
 
-        if !warp_mode {
-            for i in 0..NUM_LEDS {
-                if !keep_on.bit_test(i) {
-                    let v = &mut data[i];
-                    // let old = [v.clone(); 1];
-                    *v = brightness(core::iter::once(*v), 220).next().unwrap();
-                }
-            }
-        }
-        ws.write(brightness(data.iter().cloned(), 16)).unwrap();
-    }
-    Ok(())
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    panic!("{:#?}", ef);
 }
 
 fn input() -> &'static [&'static str] {
@@ -0,0 +1,65 @@
+#![no_main]
+#![no_std]
+
+use crate::hal::prelude::*;
+use crate::hal::spi::Spi;
+use mocca_matrix::{effects::FRAME_MS, games::Snake, input::Gestures, prelude::*};
+#[macro_use]
+extern crate cortex_m_rt as rt;
+use rt::ExceptionFrame;
+use smart_leds::{brightness, SmartLedsWrite, RGB8};
+use ws2812::Ws2812;
+use ws2812_spi as ws2812;
+extern crate panic_semihosting;
+
+// Snake: a short press on the user button turns clockwise, a long press anticlockwise. After a
+// game over the score is shown until the next press.
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut periphery) = setup() {
+        let mut gpioa = periphery.gpioa;
+        let mut gpioc = periphery.gpioc;
+        let (sck, miso, mosi) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa5.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+                gpioa.pa6.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+                gpioa.pa7.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+            )
+        });
+
+        // Configure SPI with 3Mhz rate
+        let spi = Spi::spi1(
+            periphery.spi1,
+            (sck, miso, mosi),
+            ws2812::MODE,
+            3_000_000.hz(),
+            periphery.clocks,
+            &mut periphery.apb2,
+        );
+        let mut ws = Ws2812::new(spi);
+        let button = gpioc
+            .pc13
+            .into_pull_up_input(&mut gpioc.moder, &mut gpioc.pupdr);
+
+        let mut data = [RGB8::default(); NUM_LEDS];
+        let mut gestures = Gestures::new(30, 300);
+        let mut snake = Snake::new(300, 1);
+
+        loop {
+            snake.render(&mut data);
+            ws.write(brightness(data.iter().cloned(), 32)).unwrap();
+
+            if let Some(gesture) = gestures.update(button_pressed(&button), FRAME_MS) {
+                snake.input(gesture);
+            }
+            snake.update(FRAME_MS);
+        }
+    }
+    unreachable!();
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    panic!("{:#?}", ef);
+}
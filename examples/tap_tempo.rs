@@ -0,0 +1,78 @@
+#![no_main]
+#![no_std]
+
+use crate::hal::prelude::*;
+use crate::hal::spi::Spi;
+use mocca_matrix::{
+    beat::{BeatClock, OnBeat, TapTempo},
+    color,
+    effects::{self, Direction, FRAME_MS},
+    input::Debouncer,
+    prelude::*,
+};
+#[macro_use]
+extern crate cortex_m_rt as rt;
+use rt::ExceptionFrame;
+use smart_leds::{brightness, SmartLedsWrite, RGB8};
+use ws2812::Ws2812;
+use ws2812_spi as ws2812;
+extern crate panic_semihosting;
+
+// Ripples on the beat: tap the tempo on the user button, a new wave starts on every beat.
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut periphery) = setup() {
+        let mut gpioa = periphery.gpioa;
+        let mut gpioc = periphery.gpioc;
+        let (sck, miso, mosi) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa5.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+                gpioa.pa6.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+                gpioa.pa7.into_af5(&mut gpioa.moder, &mut gpioa.afrl),
+            )
+        });
+
+        // Configure SPI with 3Mhz rate
+        let spi = Spi::spi1(
+            periphery.spi1,
+            (sck, miso, mosi),
+            ws2812::MODE,
+            3_000_000.hz(),
+            periphery.clocks,
+            &mut periphery.apb2,
+        );
+        let mut ws = Ws2812::new(spi);
+        let button = gpioc
+            .pc13
+            .into_pull_up_input(&mut gpioc.moder, &mut gpioc.pupdr);
+
+        let mut data = [RGB8::default(); NUM_LEDS];
+        let clock = BeatClock::new(120, 4);
+        let mut tap = TapTempo::new();
+        let mut debouncer = Debouncer::new(30);
+        let ripples = effects::Ripples::new(color::OCEAN, 1000, 4, Direction::Forward);
+        let mut effect = OnBeat::new(ripples, &clock, 1000);
+
+        loop {
+            effect.render(&mut data);
+            ws.write(brightness(data.iter().cloned(), 32)).unwrap();
+
+            tap.update(FRAME_MS);
+            if debouncer.update(button_pressed(&button), FRAME_MS) == Some(true) {
+                if let Some(interval) = tap.tap() {
+                    clock.set_interval(interval);
+                }
+                clock.sync();
+            }
+            clock.update(FRAME_MS);
+            effect.update(FRAME_MS);
+        }
+    }
+    unreachable!();
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    panic!("{:#?}", ef);
+}
@@ -0,0 +1,243 @@
+use crate::fixed::{cos16, isqrt, sin16};
+
+// Audio analysis on blocks of samples (e.g. microphone readings from the ADC). Everything works
+// on plain sample slices in fixed point, so it runs the same on the board and in host tests.
+
+/// In place radix-2 FFT. `re` and `im` must have the same power of two length, values should be in
+/// i16 range. Every stage is scaled by 1/2, so the result is the DFT divided by the length.
+pub fn fft(re: &mut [i32], im: &mut [i32]) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n);
+    if n < 2 {
+        return;
+    }
+
+    // bit reversed order
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (0usize.count_zeros() - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let half = size / 2;
+        let step = 65536 / size;
+        for k in 0..half {
+            // twiddle factor e^(-2 pi i k / size) in Q15
+            let angle = (k * step) as u16;
+            let wr = cos16(angle) as i32;
+            let wi = -(sin16(angle) as i32);
+            for start in (0..n).step_by(size) {
+                let a = start + k;
+                let b = a + half;
+                let tr = (wr * re[b] - wi * im[b]) >> 15;
+                let ti = (wr * im[b] + wi * re[b]) >> 15;
+                re[b] = (re[a] - tr) >> 1;
+                im[b] = (im[a] - ti) >> 1;
+                re[a] = (re[a] + tr) >> 1;
+                im[a] = (im[a] + ti) >> 1;
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// base 2 logarithm in 1/16 steps (log2_16(1024) == 160), 0 for 0
+pub fn log2_16(v: u32) -> u32 {
+    if v == 0 {
+        return 0;
+    }
+    let exp = 31 - v.leading_zeros();
+    // next four bits below the leading one as linear approximation of the fraction
+    let frac = if exp >= 4 {
+        (v >> (exp - 4)) & 0xf
+    } else {
+        (v << (4 - exp)) & 0xf
+    };
+    exp * 16 + frac
+}
+
+/// Spectrum analyzer for blocks of `N` samples (power of two)
+pub struct Analyzer<const N: usize> {
+    re: [i32; N],
+    im: [i32; N],
+}
+
+impl<const N: usize> Analyzer<N> {
+    pub fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Analyzer {
+            re: [0; N],
+            im: [0; N],
+        }
+    }
+
+    /// Hann windowed FFT of the first `N` values of `samples` (missing ones count as silence).
+    /// The DC offset of the block is removed first, so unsigned ADC readings can be passed in
+    /// directly (as long as they fit into i16).
+    pub fn process(&mut self, samples: &[i16]) {
+        let len = samples.len().min(N);
+        let mean = if len == 0 {
+            0
+        } else {
+            samples[..len].iter().map(|s| *s as i32).sum::<i32>() / len as i32
+        };
+        for i in 0..N {
+            let s = samples.get(i).map_or(0, |s| *s as i32 - mean);
+            let window = (32767 - cos16((i * 65536 / N) as u16) as i32) / 2;
+            self.re[i] = (s * window) >> 15;
+            self.im[i] = 0;
+        }
+        fft(&mut self.re, &mut self.im);
+    }
+
+    /// number of usable frequency bins (bin `i` is at `i` * sample rate / N)
+    pub fn bins(&self) -> usize {
+        N / 2
+    }
+
+    /// amplitude of frequency bin `bin` (0..N / 2) of the last processed block
+    pub fn magnitude(&self, bin: usize) -> u32 {
+        let re = self.re[bin];
+        let im = self.im[bin];
+        isqrt((re * re) as u32 + (im * im) as u32)
+    }
+
+    /// Group the bins (without DC) into `bands.len()` bands of growing width (quadratic
+    /// spacing, which comes close enough to the logarithmic hearing). Every band gets the peak
+    /// magnitude of its bins.
+    pub fn bands(&self, bands: &mut [u32]) {
+        let count = bands.len();
+        let bins = self.bins();
+        let mut lo = 1;
+        for (b, band) in bands.iter_mut().enumerate() {
+            let hi = (1 + (bins - 1) * (b + 1) * (b + 1) / (count * count))
+                .max(lo + 1)
+                .min(bins);
+            *band = (lo..hi).map(|i| self.magnitude(i)).max().unwrap_or(0);
+            lo = hi;
+        }
+    }
+}
+
+impl<const N: usize> Default for Analyzer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Band levels (0..=255) falling off smoothly, with a peak marker that is held for a while before
+/// it falls as well
+pub struct PeakMeter<const B: usize> {
+    pub levels: [u8; B],
+    pub peaks: [u8; B],
+    hold: [u32; B],
+    /// how long peaks stay up
+    pub hold_ms: u32,
+    /// fall speed of levels and peaks in level units per second
+    pub decay: u32,
+}
+
+impl<const B: usize> PeakMeter<B> {
+    pub fn new(hold_ms: u32, decay: u32) -> Self {
+        PeakMeter {
+            levels: [0; B],
+            peaks: [0; B],
+            hold: [0; B],
+            hold_ms,
+            decay,
+        }
+    }
+
+    /// feed new measurements, levels and peaks jump up immediately
+    pub fn input(&mut self, levels: &[u8]) {
+        for (i, l) in levels.iter().enumerate().take(B) {
+            self.levels[i] = self.levels[i].max(*l);
+            if *l >= self.peaks[i] {
+                self.peaks[i] = *l;
+                self.hold[i] = self.hold_ms;
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: u32) {
+        let fall = (self.decay * dt / 1000).min(255) as u8;
+        for i in 0..B {
+            self.levels[i] = self.levels[i].saturating_sub(fall);
+            if self.hold[i] > dt {
+                self.hold[i] -= dt;
+            } else {
+                self.hold[i] = 0;
+                self.peaks[i] = self.peaks[i].saturating_sub(fall).max(self.levels[i]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_audio {
+    use super::*;
+
+    fn sine(bin: usize, amplitude: i32) -> [i16; 128] {
+        let mut samples = [0; 128];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = (sin16((i * bin * 65536 / 128) as u16) as i32 * amplitude / 32767) as i16;
+        }
+        samples
+    }
+
+    #[test]
+    fn test_fft_sine() {
+        let mut analyzer = Analyzer::<128>::new();
+        for bin in [3, 10, 31, 50].iter() {
+            analyzer.process(&sine(*bin, 10000));
+            let peak = (1..64).max_by_key(|i| analyzer.magnitude(*i)).unwrap();
+            assert_eq!(peak, *bin);
+            // the window spreads the energy over the neighboring bins, but not further
+            assert!(analyzer.magnitude(*bin) > 10 * analyzer.magnitude((*bin + 4) % 64));
+        }
+    }
+
+    #[test]
+    fn test_fft_silence_and_dc() {
+        let mut analyzer = Analyzer::<64>::new();
+        analyzer.process(&[2048; 64]);
+        assert!((0..32).all(|i| analyzer.magnitude(i) == 0));
+    }
+
+    #[test]
+    fn test_bands() {
+        let mut analyzer = Analyzer::<128>::new();
+        let mut bands = [0; 11];
+        analyzer.process(&sine(2, 10000));
+        analyzer.bands(&mut bands);
+        let low = (0..11).max_by_key(|b| bands[*b]).unwrap();
+        analyzer.process(&sine(60, 10000));
+        analyzer.bands(&mut bands);
+        let high = (0..11).max_by_key(|b| bands[*b]).unwrap();
+        assert!(low < 2 && high > 8, "{} {}", low, high);
+    }
+
+    #[test]
+    fn test_log2() {
+        assert_eq!(log2_16(1), 0);
+        assert_eq!(log2_16(1024), 160);
+        assert_eq!(log2_16(3), 24);
+    }
+
+    #[test]
+    fn test_peak_meter() {
+        let mut meter = PeakMeter::<2>::new(500, 255);
+        meter.input(&[200, 0]);
+        meter.update(400);
+        assert_eq!(meter.levels[0], 200 - 102);
+        assert_eq!(meter.peaks[0], 200);
+        meter.update(1000);
+        assert_eq!(meter.levels[0], 0);
+        assert!(meter.peaks[0] < 200);
+    }
+}
@@ -0,0 +1,647 @@
+use crate::{
+    bitzet::Bitzet,
+    hex::{Cube, CUBE_DIRECTIONS},
+    led_index_oddr,
+    math::Vec2,
+    panel_cells,
+    rng::{hash32, Rng},
+    NUM_LEDS,
+};
+
+// Life-like cellular automata on the hex grid, or on the square grid for the classic rules. The
+// live cells are kept in a Bitzet, so patterns can grow beyond the panel. Cells are shown on the
+// panel through a viewport: cell v appears at odd-r position v - viewport relative to the panel
+// center (the coordinates of set_matrix_oddr). Ages are only tracked for the cells on the panel,
+// they are meant for coloring. Hashes of the last generations are kept to tell when the world
+// dies out or settles into a still life or oscillator.
+
+/// number of generations remembered for cycle detection, i.e. the longest detected period
+const HISTORY: usize = 16;
+
+/// Birth / survival rule, bit n of each mask is set if n live neighbors (0..=8) apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub birth: u16,
+    pub survive: u16,
+}
+
+/// The rule of Advent of Code 2020 day 24 ("Lobby Layout"), the original hexlife: B2/S12
+pub const LOBBY_LAYOUT: Rule = Rule::new(0b100, 0b110);
+/// Conway's Game of Life (Moore neighborhood): B3/S23
+pub const CONWAY: Rule = Rule::new(0b1000, 0b1100);
+/// HighLife (Moore neighborhood, has a replicator): B36/S23
+pub const HIGHLIFE: Rule = Rule::new(0b100_1000, 0b1100);
+
+impl Rule {
+    pub const fn new(birth: u16, survive: u16) -> Rule {
+        Rule { birth, survive }
+    }
+
+    /// Parse a rule string like "B2/S12" (case insensitive, either part may come first or be
+    /// missing). Returns None for invalid strings and for rules with B0, which would fill the
+    /// infinite plane in a single step.
+    pub fn parse(s: &str) -> Option<Rule> {
+        let mut rule = Rule::new(0, 0);
+        for part in s.split('/') {
+            let mut chars = part.chars();
+            let mask = match chars.next() {
+                Some('B') | Some('b') => &mut rule.birth,
+                Some('S') | Some('s') => &mut rule.survive,
+                _ => return None,
+            };
+            for c in chars {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 => *mask |= 1 << n,
+                    _ => return None,
+                }
+            }
+        }
+        if rule.birth & 1 != 0 {
+            return None;
+        }
+        Some(rule)
+    }
+
+    /// state of a cell in the next generation
+    pub fn next(&self, alive: bool, neighbors: usize) -> bool {
+        let mask = if alive { self.survive } else { self.birth };
+        mask & (1 << neighbors) != 0
+    }
+}
+
+/// the six neighbors of odd-r cell `v`
+pub fn neighbors(v: Vec2) -> [Vec2; 6] {
+    let c = Cube::from(v);
+    let mut n = [v; 6];
+    for (n, d) in n.iter_mut().zip(CUBE_DIRECTIONS.iter()) {
+        *n = (c + *d).into();
+    }
+    n
+}
+
+/// Which cells count as neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// the six cells around a hex cell (odd-r layout)
+    Hex,
+    /// square grid, the eight cells around including the diagonals
+    Moore,
+    /// square grid, the four orthogonal cells
+    VonNeumann,
+}
+
+const MOORE: [Vec2; 8] = [
+    Vec2 { x: 1, y: 0 },
+    Vec2 { x: 1, y: 1 },
+    Vec2 { x: 0, y: 1 },
+    Vec2 { x: -1, y: 1 },
+    Vec2 { x: -1, y: 0 },
+    Vec2 { x: -1, y: -1 },
+    Vec2 { x: 0, y: -1 },
+    Vec2 { x: 1, y: -1 },
+];
+
+impl Neighborhood {
+    /// the neighbors of `v`, only the first `count` entries are valid
+    pub fn neighbors(&self, v: Vec2) -> ([Vec2; 8], usize) {
+        let mut n = [v; 8];
+        match self {
+            Neighborhood::Hex => {
+                n[..6].copy_from_slice(&neighbors(v));
+                (n, 6)
+            }
+            Neighborhood::Moore => {
+                for (n, d) in n.iter_mut().zip(MOORE.iter()) {
+                    *n = v + *d;
+                }
+                (n, 8)
+            }
+            Neighborhood::VonNeumann => {
+                for (n, d) in n.iter_mut().zip(MOORE.iter().step_by(2)) {
+                    *n = v + *d;
+                }
+                (n, 4)
+            }
+        }
+    }
+}
+
+/// Cell reached from the origin by following `path`, a sequence of the directions e, se, sw, w,
+/// nw and ne without delimiters (the notation of Advent of Code 2020 day 24). Parsing stops at
+/// the first invalid direction.
+pub fn walk(path: &str) -> Vec2 {
+    let mut c = Cube::zero();
+    let mut chars = path.chars();
+    loop {
+        let d = match (chars.next(), chars.clone().next()) {
+            (Some('e'), _) => Cube::new(1, -1, 0),
+            (Some('w'), _) => Cube::new(-1, 1, 0),
+            (Some('s'), Some('e')) => Cube::new(0, -1, 1),
+            (Some('s'), Some('w')) => Cube::new(-1, 0, 1),
+            (Some('n'), Some('e')) => Cube::new(1, 0, -1),
+            (Some('n'), Some('w')) => Cube::new(0, 1, -1),
+            _ => break,
+        };
+        if d.z != 0 {
+            chars.next();
+        }
+        c += d;
+    }
+    c.into()
+}
+
+/// Shape of the world the automaton lives in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// The unbounded plane, except that cells with |x| or |y| >= limit relative to the viewport
+    /// are never born (keeps growing patterns within the Bitzet, which needs to hold the
+    /// viewport plus the limit)
+    Plane(i32),
+    /// only the cells shown on the panel exist, everything beyond is always dead
+    Panel,
+    /// Hexagon with the given radius around the origin, with opposite edges connected: cells
+    /// leaving one edge reappear on the opposite one. Rings up to 6 are complete on the panel,
+    /// larger hexagons have cells that are not shown. Only makes sense with the hex neighborhood.
+    Torus(i32),
+}
+
+impl Topology {
+    /// The cell `v` stands for (wrapped around for Torus), None if it is outside of the world.
+    /// Assumes the viewport at the origin, `Automaton::cell` takes the viewport into account.
+    pub fn cell(&self, v: Vec2) -> Option<Vec2> {
+        match *self {
+            Topology::Plane(limit) => {
+                if v.x.abs() < limit && v.y.abs() < limit {
+                    Some(v)
+                } else {
+                    None
+                }
+            }
+            Topology::Panel => led_index_oddr(v).map(|_| v),
+            Topology::Torus(radius) => Some(wrap(Cube::from(v), radius.max(0)).into()),
+        }
+    }
+}
+
+/// Wrap `c` into the hexagon with `radius` around the origin. The plane is tiled with copies of
+/// the hexagon, moving by the center of a neighboring copy doesn't change the cell.
+fn wrap(mut c: Cube, radius: i32) -> Cube {
+    // center of one neighboring copy, the other five are rotations by 60 degrees
+    let mirror = Cube::new(2 * radius + 1, -radius, -radius - 1);
+    while c.length() > radius {
+        let mut m = mirror;
+        let mut closest = c;
+        for _ in 0..6 {
+            let d = c - m;
+            if d.length() < closest.length() {
+                closest = d;
+            }
+            m = Cube::new(-m.z, -m.x, -m.y);
+        }
+        c = closest;
+    }
+    c
+}
+
+/// What became of the automaton, updated every step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// still changing (or in a cycle longer than the history)
+    Alive,
+    /// no live cells left
+    Empty,
+    /// same state as in the previous generation
+    Static,
+    /// same state as the given number of generations ago
+    Periodic(u32),
+}
+
+/// Life-like automaton. `N` is the Bitzet size, Bitzet<128> holds coordinates up to +-63.
+#[derive(Clone)]
+pub struct Automaton<const N: usize> {
+    cells: Bitzet<N>,
+    ages: [u8; NUM_LEDS],
+    generation: u32,
+    history: [u32; HISTORY],
+    history_len: usize,
+    status: Status,
+    pub rule: Rule,
+    /// Changing the topology does not move the live cells, clear() or reseed afterwards
+    pub topology: Topology,
+    pub neighborhood: Neighborhood,
+    viewport: Vec2,
+}
+
+impl<const N: usize> Automaton<N> {
+    pub fn new(rule: Rule) -> Self {
+        Automaton {
+            cells: Bitzet::new(),
+            ages: [0; NUM_LEDS],
+            generation: 0,
+            history: [0; HISTORY],
+            history_len: 0,
+            status: Status::Alive,
+            rule,
+            topology: Topology::Plane(15),
+            neighborhood: Neighborhood::Hex,
+            viewport: Vec2::new(0, 0),
+        }
+    }
+
+    /// cell shown at the panel center
+    pub fn viewport(&self) -> Vec2 {
+        self.viewport
+    }
+
+    /// Move the viewport, so `center` is shown at the panel center. With the hex neighborhood
+    /// only even y keep the rows aligned. The ages of the cells that come into view start over.
+    pub fn set_viewport(&mut self, center: Vec2) {
+        self.viewport = center;
+        for (led, p) in panel_cells() {
+            self.ages[led] = self.cells.contains(&(p + center)) as u8;
+        }
+    }
+
+    /// The cell `v` stands for (wrapped around for Torus), None if it is outside of the world.
+    /// The limits of Plane and Panel move along with the viewport, see `Topology::cell`.
+    pub fn cell(&self, v: Vec2) -> Option<Vec2> {
+        match self.topology {
+            Topology::Torus(_) => self.topology.cell(v),
+            _ => self.topology.cell(v - self.viewport).map(|_| v),
+        }
+    }
+
+    /// kill all cells
+    pub fn clear(&mut self) {
+        self.cells = Bitzet::new();
+        self.ages = [0; NUM_LEDS];
+        self.history_len = 0;
+        self.status = Status::Alive;
+    }
+
+    /// Replace the world with random cells, each one alive with a chance of `density` / 256.
+    /// Fills the viewport, or the whole hexagon of a torus.
+    pub fn randomize(&mut self, rng: &mut Rng, density: u8) {
+        self.clear();
+        match self.topology {
+            Topology::Torus(radius) => {
+                for r in 0..=radius {
+                    for i in 0..(6 * r).max(1) {
+                        if rng.chance(density) {
+                            self.set(Cube::ring_cell(r, i).into(), true);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for (_, v) in panel_cells() {
+                    if rng.chance(density) {
+                        self.set(v + self.viewport, true);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_alive(&self, v: Vec2) -> bool {
+        match self.cell(v) {
+            Some(v) => self.cells.contains(&v),
+            None => false,
+        }
+    }
+
+    /// Set the state of cell `v`, cells outside of the world are ignored
+    pub fn set(&mut self, v: Vec2, alive: bool) {
+        let v = match self.cell(v) {
+            Some(v) => v,
+            None => return,
+        };
+        if alive == self.cells.contains(&v) {
+            return;
+        }
+        if alive {
+            self.cells.insert(v);
+        } else {
+            self.cells.remove(&v);
+        }
+        if let Some(led) = led_index_oddr(v - self.viewport) {
+            self.ages[led] = alive as u8;
+        }
+    }
+
+    pub fn toggle(&mut self, v: Vec2) {
+        self.set(v, !self.is_alive(v));
+    }
+
+    /// live cells in z-order
+    pub fn cells(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.cells.iter()
+    }
+
+    /// number of live cells, including those beyond the panel
+    pub fn population(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// number of steps since creation
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// state detected by the last step
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// hash of the current generation
+    pub fn hash(&self) -> u32 {
+        self.cells.iter().fold(0, |h, v| {
+            hash32(h ^ hash32((v.x as u32 & 0xffff) | (v.y as u32) << 16))
+        })
+    }
+
+    /// Number of generations the cell shown by `led` has been alive (saturating at 255), 0 if it
+    /// is dead
+    pub fn age(&self, led: usize) -> u8 {
+        self.ages[led]
+    }
+
+    fn live_neighbors(&self, v: Vec2) -> usize {
+        let (n, count) = self.neighborhood.neighbors(v);
+        n[..count]
+            .iter()
+            .filter_map(|n| self.cell(*n))
+            .filter(|n| self.cells.contains(n))
+            .count()
+    }
+
+    /// advance by one generation
+    pub fn step(&mut self) {
+        let mut next = Bitzet::new();
+        for v in self.cells.iter() {
+            if self.rule.next(true, self.live_neighbors(v)) {
+                next.insert(v);
+            }
+            // only dead cells next to live ones can be born (no B0)
+            let (n, count) = self.neighborhood.neighbors(v);
+            for n in n[..count].iter().filter_map(|n| self.cell(*n)) {
+                if !self.cells.contains(&n) && self.rule.next(false, self.live_neighbors(n)) {
+                    next.insert(n);
+                }
+            }
+        }
+        for (led, p) in panel_cells() {
+            self.ages[led] = if next.contains(&(p + self.viewport)) {
+                self.ages[led].saturating_add(1)
+            } else {
+                0
+            };
+        }
+        self.cells = next;
+        self.generation = self.generation.wrapping_add(1);
+        self.detect();
+    }
+
+    fn detect(&mut self) {
+        let hash = self.hash();
+        let period = self.history[..self.history_len]
+            .iter()
+            .position(|h| *h == hash);
+        self.status = match period {
+            _ if self.cells.len() == 0 => Status::Empty,
+            Some(0) => Status::Static,
+            Some(p) => Status::Periodic(p as u32 + 1),
+            None => Status::Alive,
+        };
+        // most recent first
+        self.history.rotate_right(1);
+        self.history[0] = hash;
+        self.history_len = (self.history_len + 1).min(HISTORY);
+    }
+
+    /// advance by `generations` steps
+    pub fn run(&mut self, generations: u32) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_automaton {
+    use super::*;
+    use crate::PANEL_RADIUS;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Rule::parse("B2/S12"), Some(LOBBY_LAYOUT));
+        assert_eq!(Rule::parse("s12/b2"), Some(LOBBY_LAYOUT));
+        assert_eq!(Rule::parse("B35"), Some(Rule::new(0b101000, 0)));
+        assert_eq!(Rule::parse("B0/S12"), None);
+        assert_eq!(Rule::parse("B9"), None);
+        assert_eq!(Rule::parse("B3/S23"), Some(CONWAY));
+        assert_eq!(Rule::parse("X2"), None);
+    }
+
+    #[test]
+    fn test_walk() {
+        assert_eq!(walk("nwwswee"), Vec2::new(0, 0));
+        assert_eq!(walk("esew"), Vec2::new(0, 1));
+        for v in neighbors(Vec2::new(3, -1)).iter() {
+            assert_eq!((Cube::from(*v) - Cube::from(Vec2::new(3, -1))).length(), 1);
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.set(Vec2::new(0, 0), true);
+        life.set(Vec2::new(1, 0), true);
+        // both survive with one neighbor, the two cells next to both are born
+        life.step();
+        assert_eq!(life.population(), 4);
+        assert!(life.is_alive(walk("se")) && life.is_alive(walk("ne")));
+        let led = |v: &str| led_index_oddr(walk(v)).unwrap();
+        assert_eq!((life.age(led("")), life.age(led("se"))), (2, 1));
+        life.run(3);
+        assert_eq!(life.generation(), 4);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.topology = Topology::Plane(2);
+        life.set(Vec2::new(0, 1), true);
+        life.set(Vec2::new(1, 1), true);
+        life.step();
+        // (1, 2) would be born but is beyond the limit
+        assert_eq!(life.population(), 3);
+        assert!(!life.is_alive(Vec2::new(1, 2)));
+    }
+
+    #[test]
+    fn test_status() {
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.step();
+        assert_eq!(life.status(), Status::Empty);
+        // a single cell dies
+        life.set(Vec2::new(0, 0), true);
+        life.step();
+        assert_eq!(life.status(), Status::Empty);
+        // a triangle is a still life without births
+        life.rule = Rule::parse("S2").unwrap();
+        life.set(Vec2::new(0, 0), true);
+        life.set(Vec2::new(1, 0), true);
+        life.set(walk("se"), true);
+        life.step();
+        assert_eq!(life.population(), 3);
+        life.step();
+        assert_eq!(life.status(), Status::Static);
+        // without survival, a pair gives birth to the pair across and back
+        life.clear();
+        life.rule = Rule::parse("B2/S").unwrap();
+        life.set(Vec2::new(0, 0), true);
+        life.set(Vec2::new(1, 0), true);
+        life.run(3);
+        assert_eq!(life.status(), Status::Periodic(2));
+    }
+
+    #[test]
+    fn test_randomize() {
+        let mut rng = Rng::new(3);
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.randomize(&mut rng, 128);
+        let population = life.population();
+        assert!(population > NUM_LEDS / 3 && population < NUM_LEDS * 2 / 3);
+        life.topology = Topology::Torus(2);
+        life.randomize(&mut rng, 255);
+        assert!(life.population() > 15);
+        assert!(life.cells().all(|v| Cube::from(v).length() <= 2));
+    }
+
+    #[test]
+    fn test_panel() {
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.topology = Topology::Panel;
+        // leftmost cells of the bottom row, only the cell above both is on the panel
+        let left = panel_cells()
+            .map(|(_, v)| v)
+            .filter(|v| v.y == PANEL_RADIUS)
+            .min_by_key(|v| v.x)
+            .unwrap();
+        life.set(left, true);
+        life.set(left + Vec2::new(1, 0), true);
+        life.set(Vec2::new(0, PANEL_RADIUS + 1), true);
+        assert_eq!(life.population(), 2);
+        life.step();
+        assert_eq!(life.population(), 3);
+    }
+
+    #[test]
+    fn test_torus() {
+        let radius = 3;
+        let topology = Topology::Torus(radius);
+        let inside = |c: Cube| c.length() <= radius;
+        for x in -12..=12 {
+            for y in -12..=12 {
+                let c = Cube::new(x, y, -x - y);
+                let w = wrap(c, radius);
+                assert!(inside(w));
+                if inside(c) {
+                    assert_eq!((w.x, w.y), (c.x, c.y));
+                }
+            }
+        }
+        // every cell has six distinct neighbors, and is their neighbor in turn
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                let c = Cube::new(x, y, -x - y);
+                if !inside(c) {
+                    continue;
+                }
+                let v = Vec2::from(c);
+                let n = neighbors(v);
+                let n = n.iter().map(|n| topology.cell(*n).unwrap());
+                for a in n.clone() {
+                    assert_eq!(n.clone().filter(|b| *b == a).count(), 1);
+                    let back = neighbors(a);
+                    let back = back.iter().map(|b| topology.cell(*b).unwrap());
+                    assert_eq!(back.filter(|b| *b == v).count(), 1);
+                }
+            }
+        }
+        // a pair on the east edge gives birth on the west edge
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.topology = topology;
+        life.set(Cube::new(3, -3, 0).into(), true);
+        life.set(Cube::new(3, -2, -1).into(), true);
+        life.step();
+        assert_eq!(life.population(), 4);
+        assert!(life.is_alive(Cube::new(-3, 0, 3).into()));
+    }
+
+    #[test]
+    fn test_square() {
+        let mut life = Automaton::<128>::new(CONWAY);
+        life.neighborhood = Neighborhood::Moore;
+        // blinker
+        for x in -1..=1 {
+            life.set(Vec2::new(x, 0), true);
+        }
+        life.run(3);
+        assert_eq!(life.status(), Status::Periodic(2));
+        assert!(life.is_alive(Vec2::new(0, 1)) && !life.is_alive(Vec2::new(1, 0)));
+        // a glider moves one cell diagonally every four generations
+        life.clear();
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for (x, y) in glider.iter() {
+            life.set(Vec2::new(*x, *y), true);
+        }
+        life.run(8);
+        assert_eq!(life.population(), 5);
+        for (x, y) in glider.iter() {
+            assert!(life.is_alive(Vec2::new(x + 2, y + 2)));
+        }
+
+        let (n, count) = Neighborhood::VonNeumann.neighbors(Vec2::new(5, 5));
+        assert_eq!(count, 4);
+        assert!(n[..count]
+            .iter()
+            .all(|n| (n.x - 5).abs() + (n.y - 5).abs() == 1));
+    }
+
+    #[test]
+    fn test_viewport() {
+        let mut life = Automaton::<128>::new(CONWAY);
+        life.neighborhood = Neighborhood::Moore;
+        life.topology = Topology::Panel;
+        life.set_viewport(Vec2::new(30, 0));
+        // the world is the part of the plane in view
+        life.set(Vec2::new(0, 0), true);
+        assert!(!life.is_alive(Vec2::new(0, 0)));
+        life.set(Vec2::new(30, 0), true);
+        let center = led_index_oddr(Vec2::new(0, 0)).unwrap();
+        assert_eq!(life.age(center), 1);
+        life.set_viewport(Vec2::new(0, 0));
+        assert_eq!(life.age(center), 0);
+    }
+
+    #[test]
+    fn test_viewport_limit() {
+        // the limit of the plane moves along with the viewport
+        let mut life = Automaton::<128>::new(LOBBY_LAYOUT);
+        life.set_viewport(Vec2::new(30, 0));
+        life.randomize(&mut Rng::new(1), 128);
+        assert!(life.population() > 0);
+        assert!(life
+            .cells()
+            .all(|v| (v.x - 30).abs() < 15 && v.y.abs() < 15));
+        life.step();
+        assert!(life.population() > 0);
+        life.clear();
+        life.set(Vec2::new(30, 0), true);
+        assert_eq!(life.age(led_index_oddr(Vec2::new(0, 0)).unwrap()), 1);
+        life.set(Vec2::new(0, 0), true);
+        assert!(!life.is_alive(Vec2::new(0, 0)));
+    }
+}
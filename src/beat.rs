@@ -0,0 +1,353 @@
+use core::cell::Cell;
+
+use crate::{effects::Effect, FrameBuffer};
+
+// Musical time for effects: a beat clock that runs at a given tempo and can be pulled into sync by
+// tap tempo (user button) or by beats detected in audio sample blocks. The clock uses interior
+// mutability, so the main loop can keep updating it while effects hold shared references.
+
+/// Beat clock: phase within the current beat, beat and bar counters
+#[derive(Debug)]
+pub struct BeatClock {
+    interval_ms: Cell<u32>,
+    elapsed: Cell<u32>,
+    beats: Cell<u32>,
+    beats_per_bar: u32,
+}
+
+impl BeatClock {
+    pub fn new(bpm: u32, beats_per_bar: u32) -> Self {
+        BeatClock {
+            interval_ms: Cell::new(60_000 / bpm.max(1)),
+            elapsed: Cell::new(0),
+            beats: Cell::new(0),
+            beats_per_bar: beats_per_bar.max(1),
+        }
+    }
+
+    pub fn update(&self, dt: u32) {
+        let interval = self.interval_ms.get();
+        let mut elapsed = self.elapsed.get() + dt;
+        while elapsed >= interval {
+            elapsed -= interval;
+            self.beats.set(self.beats.get().wrapping_add(1));
+        }
+        self.elapsed.set(elapsed);
+    }
+
+    pub fn bpm(&self) -> u32 {
+        60_000 / self.interval_ms.get()
+    }
+
+    pub fn set_bpm(&self, bpm: u32) {
+        self.set_interval(60_000 / bpm.max(1));
+    }
+
+    /// duration of a beat in ms
+    pub fn interval(&self) -> u32 {
+        self.interval_ms.get()
+    }
+
+    pub fn set_interval(&self, interval_ms: u32) {
+        let interval_ms = interval_ms.max(1);
+        self.elapsed.set(self.elapsed.get().min(interval_ms - 1));
+        self.interval_ms.set(interval_ms);
+    }
+
+    /// Declare that a beat happens right now (tap, detected beat). Pulls the phase to 0, an
+    /// almost finished beat counts as the new one.
+    pub fn sync(&self) {
+        if self.elapsed.get() >= self.interval_ms.get() / 2 {
+            self.beats.set(self.beats.get().wrapping_add(1));
+        }
+        self.elapsed.set(0);
+    }
+
+    /// position within the current beat, a full beat is 65536
+    pub fn phase(&self) -> u16 {
+        (((self.elapsed.get() as u64) << 16) / self.interval_ms.get() as u64) as u16
+    }
+
+    /// number of beats since start
+    pub fn beat(&self) -> u32 {
+        self.beats.get()
+    }
+
+    /// number of bars since start
+    pub fn bar(&self) -> u32 {
+        self.beats.get() / self.beats_per_bar
+    }
+
+    /// beat within the current bar (0..beats_per_bar)
+    pub fn beat_in_bar(&self) -> u32 {
+        self.beats.get() % self.beats_per_bar
+    }
+
+    /// beats since start in 1/65536, wrapping around (use wrapping_sub for differences)
+    pub fn position(&self) -> u32 {
+        (self.beats.get() << 16) | self.phase() as u32
+    }
+}
+
+/// Tempo from the intervals between the last few taps
+#[derive(Debug, Clone)]
+pub struct TapTempo {
+    since_tap: Option<u32>,
+    intervals: [u32; 4],
+    count: usize,
+    /// a pause longer than this starts over
+    pub timeout_ms: u32,
+}
+
+impl TapTempo {
+    pub fn new() -> Self {
+        TapTempo {
+            since_tap: None,
+            intervals: [0; 4],
+            count: 0,
+            timeout_ms: 2000,
+        }
+    }
+
+    pub fn update(&mut self, dt: u32) {
+        self.since_tap = self.since_tap.map(|t| t.saturating_add(dt));
+    }
+
+    /// Register a tap, returns the averaged beat interval in ms once there are at least two taps
+    pub fn tap(&mut self) -> Option<u32> {
+        match self.since_tap {
+            Some(t) if t <= self.timeout_ms => {
+                self.intervals.rotate_right(1);
+                self.intervals[0] = t;
+                self.count = (self.count + 1).min(self.intervals.len());
+            }
+            _ => self.count = 0,
+        }
+        self.since_tap = Some(0);
+        self.interval()
+    }
+
+    /// current estimate of the beat interval
+    pub fn interval(&self) -> Option<u32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.intervals[..self.count].iter().sum::<u32>() / self.count as u32)
+        }
+    }
+}
+
+impl Default for TapTempo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const HISTORY: usize = 32;
+
+/// Energy based beat detection: a block is a beat if its energy is well above the average of the
+/// last blocks. The tempo is estimated from the intervals between detected beats.
+#[derive(Debug, Clone)]
+pub struct BeatDetector {
+    history: [u32; HISTORY],
+    pos: usize,
+    filled: usize,
+    since_beat: u32,
+    tempo: TapTempo,
+    /// energy ratio (in 1/256) above the average that counts as beat, ~1.4 works for most music
+    pub sensitivity: u32,
+    /// blocks quieter than this (mean square amplitude) are never a beat
+    pub noise_floor: u32,
+    /// shortest time between two beats
+    pub min_interval_ms: u32,
+}
+
+impl BeatDetector {
+    pub fn new() -> Self {
+        BeatDetector {
+            history: [0; HISTORY],
+            pos: 0,
+            filled: 0,
+            since_beat: 0,
+            tempo: TapTempo::new(),
+            sensitivity: 360,
+            noise_floor: 100,
+            min_interval_ms: 250,
+        }
+    }
+
+    /// Analyze a block of samples covering `block_ms`, returns true if it contains a beat
+    pub fn process(&mut self, samples: &[i16], block_ms: u32) -> bool {
+        let energy = energy(samples);
+        self.since_beat = self.since_beat.saturating_add(block_ms);
+        self.tempo.update(block_ms);
+
+        let average = if self.filled == 0 {
+            0
+        } else {
+            self.history[..self.filled]
+                .iter()
+                .map(|e| *e as u64)
+                .sum::<u64>()
+                / self.filled as u64
+        };
+        let beat = self.filled >= HISTORY / 2
+            && energy > self.noise_floor
+            && energy as u64 * 256 > average * self.sensitivity as u64
+            && self.since_beat >= self.min_interval_ms;
+
+        self.history[self.pos] = energy;
+        self.pos = (self.pos + 1) % HISTORY;
+        self.filled = (self.filled + 1).min(HISTORY);
+
+        if beat {
+            self.since_beat = 0;
+            self.tempo.tap();
+        }
+        beat
+    }
+
+    /// beat interval (ms) estimated from the last detected beats
+    pub fn interval(&self) -> Option<u32> {
+        self.tempo.interval()
+    }
+}
+
+impl Default for BeatDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// mean square amplitude of `samples` (DC offset removed)
+pub fn energy(samples: &[i16]) -> u32 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let len = samples.len() as i64;
+    let mean = samples.iter().map(|s| *s as i64).sum::<i64>() / len;
+    let sum: i64 = samples
+        .iter()
+        .map(|s| {
+            let v = *s as i64 - mean;
+            v * v
+        })
+        .sum();
+    (sum / len) as u32
+}
+
+/// Runs an effect on musical time: for every beat of `clock` the effect advances by
+/// `ms_per_beat`, independent of the actual tempo. E.g. Ripples with a period of 1000 ms and
+/// `ms_per_beat` = 1000 starts a new wave on every beat.
+pub struct OnBeat<'a, E> {
+    effect: E,
+    clock: &'a BeatClock,
+    ms_per_beat: u32,
+    last: u32,
+    frac: u64,
+}
+
+impl<'a, E: Effect> OnBeat<'a, E> {
+    pub fn new(effect: E, clock: &'a BeatClock, ms_per_beat: u32) -> Self {
+        OnBeat {
+            effect,
+            clock,
+            ms_per_beat,
+            last: clock.position(),
+            frac: 0,
+        }
+    }
+}
+
+impl<'a, E: Effect> Effect for OnBeat<'a, E> {
+    fn update(&mut self, _dt: u32) {
+        let position = self.clock.position();
+        let beats = position.wrapping_sub(self.last) as u64;
+        self.last = position;
+        let t = beats * self.ms_per_beat as u64 + self.frac;
+        self.frac = t & 0xffff;
+        self.effect.update((t >> 16) as u32);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        self.effect.render(data);
+    }
+}
+
+#[cfg(test)]
+mod test_beat {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let clock = BeatClock::new(120, 4);
+        assert_eq!(clock.interval(), 500);
+        clock.update(250);
+        assert_eq!(clock.phase(), 32768);
+        clock.update(4 * 500);
+        assert_eq!((clock.beat(), clock.bar(), clock.beat_in_bar()), (4, 1, 0));
+        // a beat slightly early
+        clock.update(200);
+        clock.sync();
+        assert_eq!((clock.beat(), clock.phase()), (5, 0));
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let mut tap = TapTempo::new();
+        assert_eq!(tap.tap(), None);
+        for interval in [400, 420, 380].iter() {
+            tap.update(*interval);
+            assert!(tap.tap().is_some());
+        }
+        assert_eq!(tap.interval(), Some(400));
+        // long pause starts over
+        tap.update(5000);
+        assert_eq!(tap.tap(), None);
+    }
+
+    #[test]
+    fn test_beat_detector() {
+        let mut detector = BeatDetector::new();
+        let quiet = [100i16, -100, 100, -100, 100, -100, 100, -100];
+        let loud = [3000i16, -3000, 3000, -3000, 3000, -3000, 3000, -3000];
+        let mut beats = 0;
+        // 10 ms blocks, a kick every 500 ms
+        for block in 0..500 {
+            let samples = if block % 50 == 0 { &loud } else { &quiet };
+            if detector.process(samples, 10) {
+                assert_eq!(block % 50, 0);
+                beats += 1;
+            }
+        }
+        assert_eq!(beats, 9);
+        assert_eq!(detector.interval(), Some(500));
+    }
+
+    struct Counter(u32);
+
+    impl Effect for Counter {
+        fn update(&mut self, dt: u32) {
+            self.0 += dt;
+        }
+        fn render(&self, _data: &mut FrameBuffer) {}
+    }
+
+    #[test]
+    fn test_on_beat() {
+        let clock = BeatClock::new(60, 4);
+        let mut effect = OnBeat::new(Counter(0), &clock, 100);
+        for _ in 0..300 {
+            clock.update(10);
+            effect.update(10);
+        }
+        assert_eq!(effect.effect.0, 300);
+        // twice the tempo, twice the speed
+        clock.set_bpm(120);
+        for _ in 0..100 {
+            clock.update(10);
+            effect.update(10);
+        }
+        assert_eq!(effect.effect.0, 500);
+    }
+}
@@ -0,0 +1,102 @@
+use core::cell::Cell;
+
+// Wall clock time for clock faces. On the board it comes from the RTC (see `setup`), on the host
+// or without a configured RTC from a `FakeClock`.
+
+const MS_PER_DAY: u32 = 24 * 60 * 60 * 1000;
+
+/// Time of day
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub millis: u16,
+}
+
+impl Time {
+    pub fn new(hours: u8, minutes: u8, seconds: u8) -> Time {
+        Time {
+            hours,
+            minutes,
+            seconds,
+            millis: 0,
+        }
+    }
+
+    /// time `ms` milliseconds after midnight (wraps around after a day)
+    pub fn from_millis(ms: u32) -> Time {
+        let ms = ms % MS_PER_DAY;
+        Time {
+            hours: (ms / 3_600_000) as u8,
+            minutes: (ms / 60_000 % 60) as u8,
+            seconds: (ms / 1000 % 60) as u8,
+            millis: (ms % 1000) as u16,
+        }
+    }
+
+    /// milliseconds since midnight
+    pub fn to_millis(&self) -> u32 {
+        ((self.hours as u32 * 60 + self.minutes as u32) * 60 + self.seconds as u32) * 1000
+            + self.millis as u32
+    }
+}
+
+pub trait Clock {
+    fn now(&self) -> Time;
+}
+
+/// Clock that only moves when told to. Used in tests, but also works as a (drifting) software
+/// clock when advanced by the frame time.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    ms: Cell<u32>,
+}
+
+impl FakeClock {
+    pub fn new(time: Time) -> Self {
+        FakeClock {
+            ms: Cell::new(time.to_millis()),
+        }
+    }
+
+    pub fn set(&self, time: Time) {
+        self.ms.set(time.to_millis());
+    }
+
+    pub fn advance(&self, ms: u32) {
+        self.ms.set((self.ms.get() + ms % MS_PER_DAY) % MS_PER_DAY);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Time {
+        Time::from_millis(self.ms.get())
+    }
+}
+
+#[cfg(test)]
+mod test_clock {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock() {
+        let clock = FakeClock::new(Time::new(23, 59, 58));
+        clock.advance(1500);
+        assert_eq!(
+            clock.now(),
+            Time {
+                millis: 500,
+                ..Time::new(23, 59, 59)
+            }
+        );
+        clock.advance(1000);
+        assert_eq!(
+            clock.now(),
+            Time {
+                millis: 500,
+                ..Time::new(0, 0, 0)
+            }
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::fixed::{cos16, sin16};
+
+// Easing curves (see easings.net) in fixed point. Progress and result are in units of `ONE`, the
+// result of elastic curves overshoots below 0 / above ONE.
+
+/// 1.0 in the fixed point format used for progress and eased values
+pub const ONE: i32 = 1 << 16;
+
+/// Shape of an easing curve, see `Easing` for the in / out variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Quad,
+    Cubic,
+    Sine,
+    Elastic,
+    Bounce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// keep the start value until the end
+    Hold,
+    /// slow start
+    In(Curve),
+    /// slow end
+    Out(Curve),
+    /// slow start and end
+    InOut(Curve),
+}
+
+impl Easing {
+    /// eased value for progress `t` (clamped to 0..=ONE)
+    pub fn apply(self, t: i32) -> i32 {
+        let t = t.clamp(0, ONE);
+        match self {
+            Easing::Linear => t,
+            Easing::Hold => {
+                if t < ONE {
+                    0
+                } else {
+                    ONE
+                }
+            }
+            Easing::In(curve) => ease_in(curve, t),
+            Easing::Out(curve) => ONE - ease_in(curve, ONE - t),
+            Easing::InOut(curve) => {
+                if t < ONE / 2 {
+                    ease_in(curve, 2 * t) / 2
+                } else {
+                    ONE - ease_in(curve, 2 * (ONE - t)) / 2
+                }
+            }
+        }
+    }
+}
+
+/// progress of `elapsed` within `duration` in units of ONE
+pub fn progress(elapsed: u32, duration: u32) -> i32 {
+    if elapsed >= duration {
+        ONE
+    } else {
+        ((elapsed as u64 * ONE as u64) / duration as u64) as i32
+    }
+}
+
+fn mul(a: i32, b: i32) -> i32 {
+    ((a as i64 * b as i64) >> 16) as i32
+}
+
+fn ease_in(curve: Curve, t: i32) -> i32 {
+    match curve {
+        Curve::Quad => mul(t, t),
+        Curve::Cubic => mul(mul(t, t), t),
+        // quarter cosine wave
+        Curve::Sine => ONE - cos16((t >> 2) as u16) as i32 * ONE / 32767,
+        // elastic and bounce are defined by their out variants
+        Curve::Elastic => ONE - elastic_out(ONE - t),
+        Curve::Bounce => ONE - bounce_out(ONE - t),
+    }
+}
+
+// 2^(-x/8) for x in 0..=8
+const EXP2_NEG: [i32; 9] = [
+    65536, 60097, 55109, 50535, 46341, 42495, 38968, 35734, 32768,
+];
+
+// 2^-x for x >= 0
+fn exp2_neg(x: i32) -> i32 {
+    let n = x >> 16;
+    if n >= 16 {
+        return 0;
+    }
+    let f = x & 0xffff;
+    let i = (f >> 13) as usize;
+    let frac = f & 0x1fff;
+    let a = EXP2_NEG[i];
+    let b = EXP2_NEG[i + 1];
+    (a + (((b - a) * frac) >> 13)) >> n
+}
+
+// 2^(-10t) * sin((10t - 0.75) * 2pi / 3) + 1
+fn elastic_out(t: i32) -> i32 {
+    if t <= 0 {
+        return 0;
+    }
+    if t >= ONE {
+        return ONE;
+    }
+    // the angle in turns is (10t - 0.75) / 3, with ONE being a full turn of sin16
+    let angle = ((10 * t - 3 * ONE / 4) / 3) as u16;
+    ONE + exp2_neg(10 * t) * sin16(angle) as i32 / 32767
+}
+
+// piecewise parabolas, the classic Penner bounce with n = 7.5625 and d = 2.75
+fn bounce_out(t: i32) -> i32 {
+    let parabola = |t: i32| ((t as i64 * t as i64 * 121 / 16) >> 16) as i32;
+    if t < ONE * 4 / 11 {
+        parabola(t)
+    } else if t < ONE * 8 / 11 {
+        parabola(t - ONE * 6 / 11) + ONE * 3 / 4
+    } else if t < ONE * 10 / 11 {
+        parabola(t - ONE * 9 / 11) + ONE * 15 / 16
+    } else {
+        parabola(t - ONE * 21 / 22) + ONE * 63 / 64
+    }
+}
+
+#[cfg(test)]
+mod test_easing {
+    use super::*;
+
+    const CURVES: [Curve; 5] = [
+        Curve::Quad,
+        Curve::Cubic,
+        Curve::Sine,
+        Curve::Elastic,
+        Curve::Bounce,
+    ];
+
+    #[test]
+    fn test_endpoints() {
+        for curve in CURVES.iter() {
+            for easing in [
+                Easing::In(*curve),
+                Easing::Out(*curve),
+                Easing::InOut(*curve),
+            ]
+            .iter()
+            {
+                assert!(easing.apply(0).abs() <= 16, "{:?}", easing);
+                assert!((easing.apply(ONE) - ONE).abs() <= 16, "{:?}", easing);
+            }
+        }
+    }
+
+    #[test]
+    fn test_monotonic() {
+        // everything but elastic and bounce only moves forward
+        for curve in [Curve::Quad, Curve::Cubic, Curve::Sine].iter() {
+            let mut prev = 0;
+            for t in (0..=ONE).step_by(256) {
+                let v = Easing::InOut(*curve).apply(t);
+                assert!(v >= prev, "{:?} {}", curve, t);
+                prev = v;
+            }
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(Easing::In(Curve::Quad).apply(ONE / 2), ONE / 4);
+        assert_eq!(Easing::Out(Curve::Quad).apply(ONE / 2), ONE * 3 / 4);
+        assert_eq!(Easing::InOut(Curve::Cubic).apply(ONE / 2), ONE / 2);
+        assert_eq!(Easing::Hold.apply(ONE - 1), 0);
+        // elastic overshoots, bounce touches the ground in between
+        assert!((0..ONE).any(|t| Easing::Out(Curve::Elastic).apply(t) > ONE));
+        assert!((Easing::Out(Curve::Bounce).apply(ONE * 4 / 11 + 1) - ONE).abs() <= 8);
+    }
+}
@@ -1,28 +1,96 @@
 use smart_leds::{brightness, SmartLedsWrite, RGB8};
 
-use crate::prelude::*;
+use crate::FrameBuffer;
 
-pub fn kitt<WS: SmartLedsWrite<Color = RGB8, Error = hal::spi::Error>>(
-    ws: &mut WS,
-    colors: &mut dyn Iterator<Item = RGB8>,
-    data: &mut [RGB8; NUM_LEDS],
-) {
-    let up = 0..MATRIX_WIDTH;
-    let down = (0..MATRIX_WIDTH).rev();
-    let pause = core::iter::repeat(20).take(100);
-    let pause_short = core::iter::repeat(20).take(20);
-    let seq = up.chain(pause_short).chain(down).chain(pause);
-    for cur in seq {
-        data.iter_mut().for_each(|v| {
-            *v = brightness(core::iter::once(*v), 210).next().unwrap();
-        });
-        if cur < MATRIX_WIDTH {
-            let c = colors.next().unwrap();
-
-            for y in 0..MATRIX_HEIGHT {
-                set_matrix(cur, y, c, data);
-            }
+pub mod basic;
+pub mod clock;
+pub mod fire;
+pub mod kitt;
+pub mod labyrinth;
+pub mod life;
+pub mod particles;
+pub mod pathfinder;
+pub mod radial;
+pub mod sand;
+mod search_animation;
+pub mod shader;
+pub mod spectrum;
+pub mod stars;
+pub mod turmite;
+
+pub use self::basic::{FadeInOut, Flash, MatrixTest, Rainbow};
+pub use self::clock::{AnalogClock, DigitalClock};
+pub use self::fire::Fire;
+pub use self::kitt::{Kitt, KittChain, Mode, Path};
+pub use self::labyrinth::Labyrinth;
+pub use self::life::{HexLife, Life};
+pub use self::particles::{Fireworks, Rain, Sparks};
+pub use self::pathfinder::Pathfinder;
+pub use self::radial::{PulsingRings, Radar, Ripples, Spiral};
+pub use self::sand::{FallingSand, Sandpile};
+pub use self::shader::{ColorWaves, Interference, Plasma, Shader};
+pub use self::spectrum::Spectrum;
+pub use self::stars::{Starfield, Twinkle};
+pub use self::turmite::Turmites;
+
+/// Approximate time it takes to push one frame out to the leds (291 * 24 bit over 3MHz SPI).
+/// Used as fixed time step by the blocking helpers.
+pub const FRAME_MS: u32 = 10;
+
+/// An effect only draws into a frame buffer and advances by explicit time steps, so a main loop
+/// can multiplex several effects with input handling and communication.
+pub trait Effect {
+    /// advance the effect by `dt` milliseconds
+    fn update(&mut self, dt: u32);
+    /// draw the current state into `data`. Every led is written, so `data` does not need to be
+    /// cleared beforehand.
+    fn render(&self, data: &mut FrameBuffer);
+    /// true if the effect has nothing more to show for now, a Playlist then moves on to the next
+    /// entry before the duration is over
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    /// map phase `p` (0..=255) according to the direction
+    pub fn apply(&self, p: u8) -> u8 {
+        match self {
+            Direction::Forward => p,
+            Direction::Backward => 255 - p,
         }
-        ws.write(brightness(data.iter().cloned(), 32)).unwrap();
     }
 }
+
+/// triangle wave: 0 -> 255 -> 0 for `p` in 0..=255
+pub(crate) fn triangle(p: u8) -> u8 {
+    if p < 128 {
+        p * 2
+    } else {
+        (255 - p) * 2
+    }
+}
+
+/// Blocking helper: run `effect` for `duration` milliseconds with a fixed time step of
+/// `FRAME_MS`, writing every frame to `ws`.
+pub fn play<WS: SmartLedsWrite<Color = RGB8>>(
+    ws: &mut WS,
+    effect: &mut dyn Effect,
+    data: &mut FrameBuffer,
+    duration: u32,
+) -> Result<(), WS::Error> {
+    let mut t = 0;
+    while t < duration {
+        effect.render(data);
+        ws.write(brightness(data.iter().cloned(), 32))?;
+        effect.update(FRAME_MS);
+        t += FRAME_MS;
+    }
+    Ok(())
+}
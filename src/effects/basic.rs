@@ -0,0 +1,238 @@
+use smart_leds::RGB8;
+
+use super::{Direction, Effect};
+use crate::{color, color::Palette, prelude::*};
+
+/// Palette cycling along the led chain
+pub struct Rainbow {
+    palette: Palette,
+    period_ms: u32,
+    direction: Direction,
+    elapsed: u32,
+}
+
+impl Rainbow {
+    /// `period_ms`: time for one full cycle through the palette
+    pub fn new(palette: Palette, period_ms: u32, direction: Direction) -> Self {
+        Rainbow {
+            palette,
+            period_ms: period_ms.max(1),
+            direction,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Rainbow {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let phase = self
+            .direction
+            .apply((self.elapsed * 256 / self.period_ms) as u8);
+        for (i, v) in data.iter_mut().enumerate() {
+            let index = (i * 256 / NUM_LEDS) as u8;
+            *v = self.palette.color(index.wrapping_add(phase));
+        }
+    }
+}
+
+/// All leds fade from black to `color` and back
+pub struct FadeInOut {
+    color: RGB8,
+    period_ms: u32,
+    elapsed: u32,
+}
+
+impl FadeInOut {
+    pub fn new(color: RGB8, period_ms: u32) -> Self {
+        FadeInOut {
+            color,
+            period_ms: period_ms.max(1),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for FadeInOut {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let p = self.elapsed * 512 / self.period_ms;
+        let level = if p < 256 { p } else { 511 - p };
+        data.fill(color::scale(self.color, level as u8));
+    }
+}
+
+/// A single led running up and down the led chain, pausing at both ends
+pub struct Flash {
+    color: RGB8,
+    step_ms: u32,
+    pause_ms: u32,
+    elapsed: u32,
+}
+
+impl Flash {
+    pub fn new(color: RGB8, step_ms: u32, pause_ms: u32) -> Self {
+        Flash {
+            color,
+            step_ms: step_ms.max(1),
+            pause_ms,
+            elapsed: 0,
+        }
+    }
+
+    fn sweep_ms(&self) -> u32 {
+        NUM_LEDS as u32 * self.step_ms
+    }
+
+    fn led(&self) -> usize {
+        let sweep = self.sweep_ms();
+        let mut t = self.elapsed;
+        if t < self.pause_ms {
+            return 0;
+        }
+        t -= self.pause_ms;
+        if t < sweep {
+            return (t / self.step_ms) as usize;
+        }
+        t -= sweep;
+        if t < self.pause_ms {
+            return NUM_LEDS - 1;
+        }
+        t -= self.pause_ms;
+        NUM_LEDS - 1 - ((t / self.step_ms) as usize).min(NUM_LEDS - 1)
+    }
+}
+
+impl Effect for Flash {
+    fn update(&mut self, dt: u32) {
+        let cycle = 2 * (self.pause_ms + self.sweep_ms());
+        self.elapsed = (self.elapsed + dt) % cycle;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        data[self.led()] = self.color;
+    }
+}
+
+/// Lights one matrix column after the other, useful to check the led mapping
+pub struct MatrixTest {
+    color: RGB8,
+    step_ms: u32,
+    elapsed: u32,
+}
+
+impl MatrixTest {
+    pub fn new(color: RGB8, step_ms: u32) -> Self {
+        MatrixTest {
+            color,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for MatrixTest {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % (self.step_ms * MATRIX_WIDTH as u32);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        let x = (self.elapsed / self.step_ms) as usize;
+        for y in 0..MATRIX_HEIGHT {
+            set_matrix(x, y, self.color, data).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_basic {
+    use super::*;
+
+    fn lit(data: &FrameBuffer) -> usize {
+        data.iter().filter(|c| **c != color::BLACK).count()
+    }
+
+    #[test]
+    fn test_rainbow() {
+        let mut data = [color::BLACK; NUM_LEDS];
+        let mut forward = Rainbow::new(Palette::Wheel, 1000, Direction::Forward);
+        let mut backward = Rainbow::new(Palette::Wheel, 1000, Direction::Backward);
+        forward.render(&mut data);
+        assert_eq!(data[0], Palette::Wheel.color(0));
+        forward.update(500);
+        backward.update(500);
+        forward.render(&mut data);
+        assert_eq!(data[0], Palette::Wheel.color(128));
+        backward.render(&mut data);
+        assert_eq!(data[0], Palette::Wheel.color(127));
+        // one period later the colors repeat
+        forward.update(1000);
+        forward.render(&mut data);
+        assert_eq!(data[0], Palette::Wheel.color(128));
+    }
+
+    #[test]
+    fn test_fade() {
+        let mut fade = FadeInOut::new(color::RED, 512);
+        let mut data = [color::WHITE; NUM_LEDS];
+        fade.render(&mut data);
+        assert_eq!(lit(&data), 0);
+        // brightest in the middle of the period, dark again at its end
+        fade.update(256);
+        fade.render(&mut data);
+        assert!(data.iter().all(|c| *c == color::scale(color::RED, 255)));
+        fade.update(255);
+        fade.render(&mut data);
+        assert_eq!(lit(&data), 0);
+    }
+
+    #[test]
+    fn test_flash() {
+        let mut flash = Flash::new(color::GREEN, 10, 100);
+        let mut data = [color::BLACK; NUM_LEDS];
+        let mut led_after = |dt: u32| {
+            flash.update(dt);
+            flash.render(&mut data);
+            assert_eq!(lit(&data), 1);
+            data.iter().position(|c| *c == color::GREEN).unwrap()
+        };
+        // pause at the start, then one led per step
+        assert_eq!(led_after(0), 0);
+        assert_eq!(led_after(99), 0);
+        assert_eq!(led_after(51), 5);
+        // pause at the end, then back down
+        let sweep = NUM_LEDS as u32 * 10;
+        assert_eq!(led_after(sweep), NUM_LEDS - 1);
+        assert_eq!(led_after(50 + 20), NUM_LEDS - 3);
+        // and from the start again after a full cycle
+        assert_eq!(led_after(2 * (100 + sweep)), NUM_LEDS - 3);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let mut matrix = MatrixTest::new(color::BLUE, 10);
+        let mut data = [color::BLACK; NUM_LEDS];
+        for x in 0..MATRIX_WIDTH + 1 {
+            matrix.render(&mut data);
+            // back to the first column after the last one
+            let x = x % MATRIX_WIDTH;
+            let mut column = 0;
+            for y in 0..MATRIX_HEIGHT {
+                if let Ok((_, c)) = get_matrix(x, y, &mut data) {
+                    assert_eq!(c, color::BLUE);
+                    column += 1;
+                }
+            }
+            assert_eq!(lit(&data), column);
+            matrix.update(10);
+        }
+    }
+}
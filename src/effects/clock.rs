@@ -0,0 +1,204 @@
+use smart_leds::RGB8;
+
+use super::Effect;
+use crate::{
+    clock::{Clock, Time},
+    color,
+    hex::{Cube, CubeLinedraw},
+    math::Vec2,
+    prelude::*,
+};
+
+// Clock faces. Both only read the clock when rendering, so update() has nothing to do.
+
+/// cube angle (counter clockwise from the right, see Cube::angle) of a clock angle (clockwise
+/// from 12 o'clock), full turn = 65536 for both
+fn clock_to_cube_angle(a: u32) -> u32 {
+    (0x4000u32.wrapping_sub(a)) & 0xffff
+}
+
+/// cell on `ring` closest to the clock angle `a` (full turn = 65536)
+fn ring_cell_at(ring: i32, a: u32) -> Cube {
+    let cells = 6 * ring as u32;
+    let index = (clock_to_cube_angle(a) * cells + 0x8000) >> 16;
+    Cube::ring_cell(ring, index as i32)
+}
+
+fn add_cell(data: &mut FrameBuffer, c: Cube, color: RGB8) {
+    if let Some(led) = led_index_oddr(c.into()) {
+        data[led] = color::add(data[led], color);
+    }
+}
+
+/// Analog clock: hour, minute and second hands from the center, hour markers on the perimeter
+pub struct AnalogClock<'a, C> {
+    clock: &'a C,
+    /// colors of hour, minute and second hands and of the markers
+    pub colors: [RGB8; 4],
+}
+
+impl<'a, C: Clock> AnalogClock<'a, C> {
+    pub fn new(clock: &'a C) -> Self {
+        AnalogClock {
+            clock,
+            colors: [
+                color::RED,
+                color::GREEN,
+                color::BLUE,
+                RGB8 {
+                    r: 64,
+                    g: 64,
+                    b: 64,
+                },
+            ],
+        }
+    }
+
+    fn hand(data: &mut FrameBuffer, a: u32, length: i32, color: RGB8) {
+        let tip = ring_cell_at(length, a);
+        // the line stops before the end point
+        for c in CubeLinedraw::new(Cube::zero(), tip) {
+            add_cell(data, c, color);
+        }
+        add_cell(data, tip, color);
+    }
+}
+
+impl<'a, C: Clock> Effect for AnalogClock<'a, C> {
+    fn update(&mut self, _dt: u32) {}
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        let t = self.clock.now();
+
+        // markers on the outermost ring that has a cell at the hour position
+        for hour in 0..12 {
+            let a = hour * 65536 / 12;
+            let marker = (1..=PANEL_RADIUS)
+                .rev()
+                .map(|r| ring_cell_at(r, a))
+                .find(|c| led_index_oddr((*c).into()).is_some());
+            if let Some(c) = marker {
+                let color = if hour % 3 == 0 {
+                    color::WHITE
+                } else {
+                    self.colors[3]
+                };
+                add_cell(data, c, color);
+            }
+        }
+
+        // hand angles, the hands move continuously
+        let ms = (t.to_millis() % (12 * 3_600_000)) as u64;
+        let hour = (ms * 65536 / (12 * 3_600_000)) as u32;
+        let minute = (ms % 3_600_000 * 65536 / 3_600_000) as u32;
+        let second = (ms % 60_000 * 65536 / 60_000) as u32;
+        Self::hand(data, hour, PANEL_RADIUS / 2, self.colors[0]);
+        Self::hand(data, minute, PANEL_RADIUS - 3, self.colors[1]);
+        Self::hand(data, second, PANEL_RADIUS - 1, self.colors[2]);
+    }
+}
+
+/// 3x5 digit font, one row per byte (top first), bit 2 is the left column
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// draw `digit` with its top left corner at odd-r position `pos` (relative to the panel center)
+pub fn draw_digit(data: &mut FrameBuffer, digit: u8, pos: Vec2, color: RGB8) {
+    for (y, row) in DIGITS[digit as usize % 10].iter().enumerate() {
+        for x in 0..3 {
+            if row & (0b100 >> x) != 0 {
+                set_matrix_oddr(Vec2::new(pos.x + x, pos.y + y as i32), color, data);
+            }
+        }
+    }
+}
+
+/// Digital clock: hours above minutes, the center cell blinks with the seconds
+pub struct DigitalClock<'a, C> {
+    clock: &'a C,
+    pub hours_color: RGB8,
+    pub minutes_color: RGB8,
+}
+
+impl<'a, C: Clock> DigitalClock<'a, C> {
+    pub fn new(clock: &'a C) -> Self {
+        DigitalClock {
+            clock,
+            hours_color: color::CYAN,
+            minutes_color: color::MAGENTA,
+        }
+    }
+}
+
+impl<'a, C: Clock> Effect for DigitalClock<'a, C> {
+    fn update(&mut self, _dt: u32) {}
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        let Time {
+            hours,
+            minutes,
+            seconds,
+            ..
+        } = self.clock.now();
+        // two 3x5 digits with one column space each, hours above and minutes below the center
+        draw_digit(data, hours / 10, Vec2::new(-3, -6), self.hours_color);
+        draw_digit(data, hours % 10, Vec2::new(1, -6), self.hours_color);
+        draw_digit(data, minutes / 10, Vec2::new(-3, 2), self.minutes_color);
+        draw_digit(data, minutes % 10, Vec2::new(1, 2), self.minutes_color);
+        if seconds % 2 == 0 {
+            set_matrix_oddr(Vec2::new(0, 0), color::WHITE, data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_clock_faces {
+    use super::*;
+    use crate::{clock::FakeClock, hex::PIXEL_UNIT};
+
+    fn lit(data: &FrameBuffer) -> usize {
+        data.iter().filter(|c| **c != color::BLACK).count()
+    }
+
+    #[test]
+    fn test_analog_noon() {
+        let clock = FakeClock::new(Time::new(12, 0, 0));
+        let face = AnalogClock::new(&clock);
+        let mut data = [color::BLACK; NUM_LEDS];
+        face.render(&mut data);
+        // all hands point straight up
+        for r in 1..PANEL_RADIUS - 1 {
+            let up = ring_cell_at(r, 0);
+            assert!(up.to_pixel().0.abs() <= PIXEL_UNIT / 2);
+            assert_ne!(data[led_index_oddr(up.into()).unwrap()], color::BLACK);
+        }
+        // 3 o'clock is to the right
+        let right = ring_cell_at(4, 65536 / 4);
+        assert_eq!((right.x, right.y, right.z), (4, -4, 0));
+    }
+
+    #[test]
+    fn test_digital() {
+        let clock = FakeClock::new(Time::new(12, 34, 1));
+        let face = DigitalClock::new(&clock);
+        let mut data = [color::BLACK; NUM_LEDS];
+        face.render(&mut data);
+        let bits = |d: usize| -> usize { DIGITS[d].iter().map(|r| r.count_ones() as usize).sum() };
+        assert_eq!(lit(&data), bits(1) + bits(2) + bits(3) + bits(4));
+        clock.advance(1000);
+        face.render(&mut data);
+        assert_eq!(lit(&data), bits(1) + bits(2) + bits(3) + bits(4) + 1);
+    }
+}
@@ -0,0 +1,108 @@
+use super::Effect;
+use crate::{color::Palette, prelude::*, rng::Rng};
+
+// Fire2012 (classic 1d heat propagation fire) adapted to the hex grid: heat lives in matrix
+// coordinates, every step it is cooled randomly and drifts upwards by taking the heat of the two
+// neighbors below (odd-r layout) and of the cell two rows below. New sparks are ignited at the
+// bottom cell of each column.
+
+/// duration of one simulation step (50 steps per second)
+pub const STEP_MS: u32 = 20;
+
+pub struct Fire {
+    heat: [[u8; MATRIX_WIDTH]; MATRIX_HEIGHT],
+    bottom: [usize; MATRIX_WIDTH],
+    palette: Palette,
+    cooling: u8,
+    sparking: u8,
+    rng: Rng,
+    elapsed: u32,
+}
+
+impl Fire {
+    /// `cooling`: how fast flames cool down (Fire2012 uses 55, higher -> shorter flames),
+    /// `sparking`: chance (out of 255) for a new spark per column and step
+    pub fn new(palette: Palette, cooling: u8, sparking: u8, seed: u32) -> Self {
+        // lowest panel cell in every column
+        let mut bottom = [0; MATRIX_WIDTH];
+        for (_, v) in panel_cells() {
+            let x = (v.x + PANEL_RADIUS) as usize;
+            let y = (v.y + PANEL_RADIUS) as usize;
+            bottom[x] = bottom[x].max(y);
+        }
+        Fire {
+            heat: [[0; MATRIX_WIDTH]; MATRIX_HEIGHT],
+            bottom,
+            palette,
+            cooling,
+            sparking,
+            rng: Rng::new(seed),
+            elapsed: 0,
+        }
+    }
+
+    fn step(&mut self) {
+        let max_cooling = self.cooling as u32 * 10 / MATRIX_HEIGHT as u32 + 2;
+        for row in self.heat.iter_mut() {
+            for h in row.iter_mut() {
+                *h = h.saturating_sub(self.rng.range(max_cooling) as u8);
+            }
+        }
+
+        // top to bottom, so every row still sees the old heat of the rows below
+        for y in 0..MATRIX_HEIGHT - 1 {
+            for x in 0..MATRIX_WIDTH {
+                // odd-r: odd rows are shifted right
+                let shift = y % 2;
+                let below = &self.heat[y + 1];
+                let left = (x + shift).checked_sub(1).map_or(0, |x| below[x] as u32);
+                let right = below.get(x + shift).map_or(0, |h| *h as u32);
+                self.heat[y][x] = match self.heat.get(y + 2) {
+                    Some(below2) => ((left + right + 2 * below2[x] as u32) / 4) as u8,
+                    None => ((left + right) / 2) as u8,
+                };
+            }
+        }
+
+        for x in 0..MATRIX_WIDTH {
+            if self.rng.chance(self.sparking) {
+                let h = &mut self.heat[self.bottom[x]][x];
+                *h = h.saturating_add(160 + self.rng.range(96) as u8);
+            }
+        }
+    }
+}
+
+impl Effect for Fire {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= STEP_MS {
+            self.elapsed -= STEP_MS;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        for (led, v) in panel_cells() {
+            let h = self.heat[(v.y + PANEL_RADIUS) as usize][(v.x + PANEL_RADIUS) as usize];
+            data[led] = self.palette.color(h);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_fire {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn test_heat_rises_from_bottom() {
+        let mut fire = Fire::new(color::HEAT, 55, 60, 1);
+        fire.update(STEP_MS * 500);
+        let row_heat = |rows: core::ops::Range<usize>| -> u32 {
+            rows.map(|y| fire.heat[y].iter().map(|h| *h as u32).sum::<u32>())
+                .sum()
+        };
+        assert!(row_heat(14..21) > row_heat(0..7));
+    }
+}
@@ -0,0 +1,365 @@
+use smart_leds::RGB8;
+
+use super::{Effect, FRAME_MS};
+use crate::{
+    color::{self, Palette},
+    hex::{Axis, Cube, CUBE_DIRECTIONS},
+    math::Vec2,
+    prelude::*,
+};
+
+const PAUSE_SHORT: u32 = 20;
+const PAUSE: u32 = 100;
+const STEPS: u32 = MATRIX_WIDTH as u32 + PAUSE_SHORT + MATRIX_WIDTH as u32 + PAUSE;
+/// duration of a single scanner step
+pub const STEP_MS: u32 = FRAME_MS;
+/// duration of one full sweep up and down with the default settings, including the pauses
+pub const CYCLE_MS: u32 = STEPS * STEP_MS;
+
+/// most positions a path can have (the outer border has 60 cells)
+const MAX_POSITIONS: usize = 64;
+const NO_POSITION: u8 = 0xff;
+
+/// What the scanner sweeps along. Every led belongs to (at most) one position on the path, all
+/// leds of the current position are lit at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Path {
+    /// matrix columns from left to right (the original scanner)
+    Columns,
+    /// lines of constant cube coordinate along one of the hex axes
+    Axis(Axis),
+    /// rings from the center outwards
+    Radial,
+    /// the cells on the border of the panel, counter clockwise
+    Perimeter,
+}
+
+impl Path {
+    /// position of every led on the path and the number of positions
+    fn positions(self) -> ([u8; NUM_LEDS], usize) {
+        let mut map = [NO_POSITION; NUM_LEDS];
+        let mut count = 0;
+        let on_panel = |c: Cube| led_index_oddr(c.into()).is_some();
+        if self == Path::Perimeter {
+            // consecutive positions for the border cells, in the order of their angles
+            let border = || {
+                panel_cells()
+                    .map(|(led, v)| (led, Cube::from(v)))
+                    .filter(|(_, c)| CUBE_DIRECTIONS.iter().any(|d| !on_panel(*c + *d)))
+            };
+            for (led, c) in border() {
+                let key = (self.raw_position(c), led);
+                let before = border().filter(|(l, b)| (self.raw_position(*b), *l) < key);
+                map[led] = before.count() as u8;
+                count += 1;
+            }
+            return (map, count);
+        }
+        let min = panel_cells()
+            .map(|(_, v)| self.raw_position(Cube::from(v)))
+            .min()
+            .unwrap_or(0);
+        for (led, v) in panel_cells() {
+            let p = (self.raw_position(Cube::from(v)) - min) as usize;
+            map[led] = p as u8;
+            count = count.max(p + 1);
+        }
+        (map, count)
+    }
+
+    fn raw_position(self, c: Cube) -> i32 {
+        match self {
+            Path::Columns => Vec2::from(c).x,
+            Path::Axis(axis) => axis.coord(&c),
+            Path::Radial => c.length(),
+            // only used for sorting, see `positions`
+            Path::Perimeter => c.angle() as i32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// back and forth
+    Bounce,
+    /// always in the same direction, starting over at the beginning
+    Loop,
+}
+
+/// Where the scanner takes its colors from: iterators yield one color per step, palettes are
+/// indexed by the position on the path.
+pub trait ColorSource {
+    fn next_color(&mut self, position: usize, positions: usize) -> RGB8;
+}
+
+impl<I: Iterator<Item = RGB8>> ColorSource for I {
+    fn next_color(&mut self, _position: usize, _positions: usize) -> RGB8 {
+        self.next().unwrap_or(color::BLACK)
+    }
+}
+
+impl ColorSource for Palette {
+    fn next_color(&mut self, position: usize, positions: usize) -> RGB8 {
+        self.color((position * 255 / positions.max(2).saturating_sub(1)) as u8)
+    }
+}
+
+/// Knight Rider style scanner: by default sweeps full columns across the matrix and back, taking
+/// a new color from `colors` for every column and leaving a fading trail. Path, mode, trail,
+/// speed and pauses can be changed with the builder methods.
+pub struct Kitt<S> {
+    colors: S,
+    path: Path,
+    mode: Mode,
+    map: [u8; NUM_LEDS],
+    positions: usize,
+    fade: u8,
+    step_ms: u32,
+    pauses: (u32, u32),
+    cells: [RGB8; MAX_POSITIONS],
+    step: u32,
+    elapsed: u32,
+}
+
+impl<S: ColorSource> Kitt<S> {
+    pub fn new(colors: S) -> Self {
+        let path = Path::Columns;
+        let (map, positions) = path.positions();
+        Kitt {
+            colors,
+            path,
+            mode: Mode::Bounce,
+            map,
+            positions,
+            fade: 210,
+            step_ms: STEP_MS,
+            pauses: (PAUSE_SHORT, PAUSE),
+            cells: [color::BLACK; MAX_POSITIONS],
+            step: 0,
+            elapsed: 0,
+        }
+    }
+
+    pub fn path(mut self, path: Path) -> Self {
+        self.path = path;
+        let (map, positions) = path.positions();
+        self.map = map;
+        self.positions = positions;
+        self.step = 0;
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self.step = 0;
+        self
+    }
+
+    /// trail length: a position fades to about 1/16 within `steps` steps
+    pub fn trail(mut self, steps: u32) -> Self {
+        // (1 - x)^n ~ e^(-nx) and e^-2.8 ~ 1/16
+        self.fade = 255 - (716 / steps.max(3)) as u8;
+        self
+    }
+
+    /// speed: duration of a single step
+    pub fn step_ms(mut self, step_ms: u32) -> Self {
+        self.step_ms = step_ms.max(1);
+        self
+    }
+
+    /// number of steps to wait at the end and (when bouncing) at the start of the path
+    pub fn pauses(mut self, end: u32, start: u32) -> Self {
+        self.pauses = (end, start);
+        self
+    }
+
+    /// duration of one full cycle
+    pub fn cycle_ms(&self) -> u32 {
+        self.cycle_steps() * self.step_ms
+    }
+
+    fn cycle_steps(&self) -> u32 {
+        let n = self.positions as u32;
+        match self.mode {
+            Mode::Bounce => n + self.pauses.0 + n + self.pauses.1,
+            Mode::Loop => n + self.pauses.0,
+        }
+    }
+
+    // sequence: forward, pause, (backward, pause)
+    fn position(&self, step: u32) -> Option<usize> {
+        let n = self.positions as u32;
+        let back = n + self.pauses.0;
+        if step < n {
+            Some(step as usize)
+        } else if self.mode == Mode::Bounce && step >= back && step < back + n {
+            Some((n - 1 - (step - back)) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn step(&mut self) {
+        let fade = self.fade;
+        self.cells
+            .iter_mut()
+            .for_each(|v| *v = color::scale(*v, fade));
+
+        if let Some(p) = self.position(self.step) {
+            self.cells[p] = self.colors.next_color(p, self.positions);
+        }
+        self.step = (self.step + 1) % self.cycle_steps();
+    }
+}
+
+impl<S: ColorSource> Effect for Kitt<S> {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        for (v, p) in data.iter_mut().zip(self.map.iter()) {
+            *v = match self.cells.get(*p as usize) {
+                Some(c) => *c,
+                None => color::BLACK,
+            };
+        }
+    }
+}
+
+const CHAIN_PAUSE_SHORT: usize = 2;
+const CHAIN_PAUSE: usize = 8;
+const CHAIN_STEPS: usize = NUM_LEDS + CHAIN_PAUSE_SHORT + NUM_LEDS + CHAIN_PAUSE;
+
+/// The original single led scanner running up and down the led chain. Full brightness lags
+/// behind one step (simulates the turn on time of 80s lightbulbs).
+pub struct KittChain {
+    color: RGB8,
+    step_ms: u32,
+    leds: FrameBuffer,
+    step: usize,
+    prev: Option<usize>,
+    elapsed: u32,
+}
+
+impl KittChain {
+    pub fn new(color: RGB8, step_ms: u32) -> Self {
+        KittChain {
+            color,
+            step_ms: step_ms.max(1),
+            leds: [color::BLACK; NUM_LEDS],
+            step: 0,
+            prev: None,
+            elapsed: 0,
+        }
+    }
+
+    fn led(step: usize) -> Option<usize> {
+        let down = NUM_LEDS + CHAIN_PAUSE_SHORT;
+        if step < NUM_LEDS {
+            Some(step)
+        } else if step >= down && step < down + NUM_LEDS {
+            Some(NUM_LEDS - 1 - (step - down))
+        } else {
+            None
+        }
+    }
+
+    fn step(&mut self) {
+        self.leds.iter_mut().for_each(|v| *v = color::scale(*v, 64));
+
+        let cur = Self::led(self.step);
+        if let Some(prev) = self.prev {
+            self.leds[prev] = self.color;
+        }
+        if let Some(cur) = cur {
+            self.leds[cur] = color::scale(self.color, 128);
+        }
+        self.prev = cur;
+        self.step = (self.step + 1) % CHAIN_STEPS;
+    }
+}
+
+impl Effect for KittChain {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.copy_from_slice(&self.leds);
+    }
+}
+
+#[cfg(test)]
+mod test_kitt {
+    use super::*;
+
+    #[test]
+    fn test_default_is_column_scanner() {
+        let kitt = Kitt::new(Palette::Wheel);
+        assert_eq!(kitt.positions, MATRIX_WIDTH);
+        assert_eq!(kitt.cycle_ms(), CYCLE_MS);
+    }
+
+    #[test]
+    fn test_radial_loop() {
+        let mut kitt = Kitt::new(core::iter::repeat(color::WHITE))
+            .path(Path::Radial)
+            .mode(Mode::Loop)
+            .pauses(0, 0)
+            .step_ms(10);
+        assert_eq!(kitt.cycle_ms(), 11 * 10);
+        let mut data = [color::BLACK; NUM_LEDS];
+        kitt.update(10);
+        kitt.render(&mut data);
+        // only the center is lit after the first step
+        assert_eq!(data.iter().filter(|c| **c != color::BLACK).count(), 1);
+        // and it starts over after a full cycle
+        kitt.update(110);
+        kitt.render(&mut data);
+        assert_eq!(data.iter().filter(|c| **c == color::WHITE).count(), 1);
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut chain = KittChain::new(color::WHITE, 10);
+        let mut data = [color::BLACK; NUM_LEDS];
+        chain.update(10);
+        chain.render(&mut data);
+        assert_eq!(data[0], color::scale(color::WHITE, 128));
+        // full brightness lags one step behind, the trail fades
+        chain.update(20);
+        chain.render(&mut data);
+        assert_eq!(data[1], color::WHITE);
+        assert_eq!(data[2], color::scale(color::WHITE, 128));
+        assert_eq!(data[0], color::scale(color::WHITE, 64));
+        // at the top of the chain it turns around after the short pause
+        chain.update(10 * (NUM_LEDS - 3 + CHAIN_PAUSE_SHORT + 1) as u32);
+        chain.render(&mut data);
+        assert_eq!(data[NUM_LEDS - 1], color::scale(color::WHITE, 128));
+    }
+
+    #[test]
+    fn test_perimeter() {
+        let (map, positions) = Path::Perimeter.positions();
+        assert_eq!(positions, 60);
+        for (led, v) in panel_cells() {
+            if Cube::from(v).length() < 6 {
+                assert_eq!(map[led], NO_POSITION);
+            }
+        }
+        // one led per position, so the scanner head never disappears
+        for p in 0..positions {
+            assert_eq!(map.iter().filter(|m| **m as usize == p).count(), 1);
+        }
+    }
+}
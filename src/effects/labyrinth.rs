@@ -0,0 +1,153 @@
+use smart_leds::RGB8;
+
+use super::{search_animation::SearchAnimation, Effect};
+use crate::{
+    color::{self, Palette},
+    hex::Cube,
+    maze::{self, Generator, Mark, Maze},
+    panel_cells,
+    rng::Rng,
+    search::Algorithm,
+    FrameBuffer,
+};
+
+// A maze is carved into the panel step by step, then A* finds the way between two random nodes,
+// coloring the visited cells by their distance from the start, and the path is traced. After a
+// while a new maze is carved.
+
+/// time the solved maze stays visible
+const HOLD_MS: u32 = 3000;
+
+const WALL: RGB8 = RGB8 { r: 0, g: 0, b: 48 };
+
+/// random maze node on the panel
+fn random_node(rng: &mut Rng) -> Cube {
+    let count = panel_cells()
+        .filter(|(_, v)| maze::is_node((*v).into()))
+        .count() as u32;
+    let i = rng.range(count) as usize;
+    panel_cells()
+        .filter(|(_, v)| maze::is_node((*v).into()))
+        .nth(i)
+        .map(|(_, v)| v.into())
+        .unwrap_or_else(Cube::zero)
+}
+
+pub struct Labyrinth {
+    maze: Maze,
+    /// the solver, only meaningful once the maze is carved
+    animation: SearchAnimation,
+    carving: bool,
+    palette: Palette,
+    step_ms: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Labyrinth {
+    /// `palette` colors the cells visited by the solver by distance, `step_ms`: time per carving
+    /// or solving step
+    pub fn new(generator: Generator, palette: Palette, step_ms: u32, seed: u32) -> Self {
+        let mut rng = Rng::new(seed);
+        let maze = Maze::new(generator, rng.next_u32());
+        let animation = SearchAnimation::new(Algorithm::AStar, HOLD_MS, &mut rng, random_node);
+        Labyrinth {
+            maze,
+            animation,
+            carving: true,
+            palette,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            rng,
+        }
+    }
+
+    pub fn maze(&self) -> &Maze {
+        &self.maze
+    }
+
+    fn step(&mut self) {
+        if self.carving {
+            if !self.maze.step() {
+                self.animation =
+                    SearchAnimation::new(Algorithm::AStar, HOLD_MS, &mut self.rng, random_node);
+                self.carving = false;
+            }
+        } else {
+            let maze = &self.maze;
+            if !self.animation.step(|c| maze.cost(c), self.step_ms) {
+                self.maze = Maze::new(self.maze.generator(), self.rng.next_u32());
+                self.carving = true;
+            }
+        }
+    }
+}
+
+impl Effect for Labyrinth {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let search = self.animation.search();
+        let solving = !self.carving;
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            data[led] = match self.maze.mark(c) {
+                Mark::Wall => WALL,
+                Mark::Active => color::WHITE,
+                Mark::Passage if solving && search.is_frontier(c) => color::WHITE,
+                Mark::Passage if solving && search.is_visited(c) => {
+                    let cost = search.cost(c).unwrap_or(0);
+                    self.palette.color((cost * 4).min(255) as u8)
+                }
+                Mark::Passage => color::BLACK,
+            };
+        }
+        if solving {
+            self.animation.render(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_labyrinth {
+    use super::*;
+    use crate::{search::Status, NUM_LEDS};
+
+    #[test]
+    fn test_cycle() {
+        let mut labyrinth = Labyrinth::new(Generator::Backtracker, Palette::Wheel, 10, 1);
+        let mut data = [color::BLACK; NUM_LEDS];
+        labyrinth.update(10);
+        labyrinth.render(&mut data);
+        assert!(data.contains(&WALL) && data.contains(&color::WHITE));
+        // carving takes a few hundred steps, then the solver takes over
+        for _ in 0..2 * NUM_LEDS {
+            labyrinth.update(10);
+            if !labyrinth.carving {
+                break;
+            }
+        }
+        assert!(!labyrinth.carving);
+        assert!(labyrinth.maze().is_done());
+        let animation = &labyrinth.animation;
+        assert!(maze::is_node(animation.start()) && maze::is_node(animation.goal()));
+        assert_eq!(animation.search().status(), Status::Searching);
+        // solving, tracing and holding, then a new maze
+        let mut solved = false;
+        for _ in 0..2 * NUM_LEDS + HOLD_MS as usize / 10 {
+            labyrinth.update(10);
+            solved |= labyrinth.animation.search().status() == Status::Found;
+            if labyrinth.carving {
+                break;
+            }
+        }
+        assert!(solved && labyrinth.carving);
+        assert!(!labyrinth.maze().is_done());
+    }
+}
@@ -0,0 +1,174 @@
+use super::Effect;
+use crate::{
+    automaton::{Automaton, Status},
+    color::{self, Palette},
+    prelude::*,
+    rng::Rng,
+};
+
+// Cellular automaton as effect. Live cells are colored by age, dead cells fade out over a few
+// generations.
+
+/// What to do when the automaton dies out or gets stuck in a still life or oscillator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// keep going
+    Ignore,
+    /// start over with random cells of the given density (out of 256)
+    Reseed(u8),
+    /// Report the effect as finished, so a Playlist switches to the next entry, and reseed on
+    /// the next update
+    Finish(u8),
+}
+
+/// Runs an automaton with one generation per `step_ms`
+pub struct Life<const N: usize> {
+    automaton: Automaton<N>,
+    palette: Palette,
+    age_step: u8,
+    fade: u8,
+    step_ms: u32,
+    elapsed: u32,
+    frame: FrameBuffer,
+    action: Action,
+    patience: u32,
+    stuck: u32,
+    finished: bool,
+    rng: Rng,
+}
+
+/// former name of `Life`, from when only the hex neighborhood was supported
+pub type HexLife<const N: usize> = Life<N>;
+
+impl<const N: usize> Life<N> {
+    /// `palette` is indexed by cell age (see `age_step`), use a solid palette to ignore the age
+    pub fn new(automaton: Automaton<N>, palette: Palette, step_ms: u32, seed: u32) -> Self {
+        let mut life = Life {
+            automaton,
+            palette,
+            age_step: 16,
+            fade: 128,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            frame: [color::BLACK; NUM_LEDS],
+            action: Action::Ignore,
+            patience: 0,
+            stuck: 0,
+            finished: false,
+            rng: Rng::new(seed),
+        };
+        life.draw();
+        life
+    }
+
+    /// palette index increment per generation a cell has been alive
+    pub fn age_step(mut self, step: u8) -> Self {
+        self.age_step = step;
+        self.draw();
+        self
+    }
+
+    /// brightness (out of 255) dead cells keep per generation, 0 for no trails
+    pub fn trail(mut self, fade: u8) -> Self {
+        self.fade = fade;
+        self
+    }
+
+    /// `action` to take once the automaton has been empty, static or periodic for `patience`
+    /// generations
+    pub fn on_stagnation(mut self, action: Action, patience: u32) -> Self {
+        self.action = action;
+        self.patience = patience;
+        self
+    }
+
+    pub fn automaton(&self) -> &Automaton<N> {
+        &self.automaton
+    }
+
+    pub fn automaton_mut(&mut self) -> &mut Automaton<N> {
+        &mut self.automaton
+    }
+
+    fn check_stagnation(&mut self) {
+        if self.automaton.status() == Status::Alive {
+            self.stuck = 0;
+            return;
+        }
+        self.stuck += 1;
+        if self.stuck <= self.patience {
+            return;
+        }
+        self.stuck = 0;
+        match self.action {
+            Action::Ignore => (),
+            Action::Reseed(density) => self.automaton.randomize(&mut self.rng, density),
+            Action::Finish(_) => self.finished = true,
+        }
+    }
+
+    fn draw(&mut self) {
+        for led in 0..NUM_LEDS {
+            let age = self.automaton.age(led);
+            self.frame[led] = if age > 0 {
+                let index = (age as u32 - 1) * self.age_step as u32;
+                self.palette.color(index.min(255) as u8)
+            } else {
+                color::scale(self.frame[led], self.fade)
+            };
+        }
+    }
+}
+
+impl<const N: usize> Effect for Life<N> {
+    fn update(&mut self, dt: u32) {
+        // the stagnated world stays visible while the effect is reported as finished
+        if self.finished {
+            self.finished = false;
+            if let Action::Finish(density) = self.action {
+                self.automaton.randomize(&mut self.rng, density);
+                self.draw();
+            }
+        }
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms && !self.finished {
+            self.elapsed -= self.step_ms;
+            self.automaton.step();
+            self.check_stagnation();
+            self.draw();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.copy_from_slice(&self.frame);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod test_life {
+    use super::*;
+    use crate::{automaton::LOBBY_LAYOUT, math::Vec2};
+
+    #[test]
+    fn test_reseed() {
+        let mut automaton = Automaton::<128>::new(LOBBY_LAYOUT);
+        automaton.set(Vec2::new(0, 0), true);
+        let mut life =
+            Life::new(automaton, Palette::Wheel, 100, 1).on_stagnation(Action::Finish(64), 2);
+        // the single cell dies in the first generation, then the empty world is kept for two
+        life.update(200);
+        assert!(!life.is_finished());
+        assert_eq!(life.automaton().population(), 0);
+        life.update(100);
+        assert!(life.is_finished());
+        assert_eq!(life.automaton().population(), 0);
+        // further steps wait for the next update, which reseeds
+        life.update(10);
+        assert!(!life.is_finished());
+        assert!(life.automaton().population() > 0);
+    }
+}
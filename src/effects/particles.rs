@@ -0,0 +1,189 @@
+use super::Effect;
+use crate::{
+    color::{self, Palette},
+    hex::PIXEL_UNIT,
+    particles::{Boundary, Emitter, Particle, ParticleSystem},
+    rng::Rng,
+    FrameBuffer, PANEL_RADIUS,
+};
+
+// Effects built on the particle system (particles.rs)
+
+/// bottom / top border of the panel in pixel coordinates
+const EDGE_Y: i32 = PANEL_RADIUS * 222 * PIXEL_UNIT / 256;
+
+/// Rockets launched from the bottom, exploding into bursts of sparks
+pub struct Fireworks {
+    rockets: ParticleSystem<4>,
+    sparks: ParticleSystem<160>,
+    palette: Palette,
+    interval_ms: u32,
+    next_launch: u32,
+    rng: Rng,
+}
+
+impl Fireworks {
+    /// `interval_ms`: average time between two launches
+    pub fn new(palette: Palette, interval_ms: u32, seed: u32) -> Self {
+        let gravity = (0, 8 * PIXEL_UNIT);
+        Fireworks {
+            rockets: ParticleSystem::new(gravity, 0, Boundary::Open),
+            sparks: ParticleSystem::new(gravity, 96, Boundary::Open),
+            palette,
+            interval_ms: interval_ms.max(1),
+            next_launch: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn launch(&mut self) {
+        let rng = &mut self.rng;
+        let life = rng.between(900, 1300) as u16;
+        self.rockets.spawn(Particle {
+            x: rng.between(-6 * PIXEL_UNIT, 6 * PIXEL_UNIT),
+            y: EDGE_Y,
+            vx: rng.between(-PIXEL_UNIT, PIXEL_UNIT),
+            vy: -rng.between(13 * PIXEL_UNIT, 16 * PIXEL_UNIT),
+            life,
+            max_life: life * 2,
+            color: color::WHITE,
+        });
+    }
+
+    fn explode(&mut self, x: i32, y: i32) {
+        let mut emitter = Emitter::new(
+            x,
+            y,
+            0,
+            Palette::Solid(self.palette.color(self.rng.next_u32() as u8)),
+        );
+        emitter.speed = (2 * PIXEL_UNIT, 6 * PIXEL_UNIT);
+        emitter.life = (600, 1400);
+        for _ in 0..40 {
+            self.sparks.spawn(emitter.particle(&mut self.rng));
+        }
+    }
+}
+
+impl Effect for Fireworks {
+    fn update(&mut self, dt: u32) {
+        let mut bursts = [None; 4];
+        for (rocket, burst) in self.rockets.iter().zip(bursts.iter_mut()) {
+            if rocket.life as u32 <= dt {
+                *burst = Some((rocket.x, rocket.y));
+            }
+        }
+        for (x, y) in bursts.iter().flatten() {
+            self.explode(*x, *y);
+        }
+        self.rockets.update(dt);
+        self.sparks.update(dt);
+
+        self.next_launch = self.next_launch.saturating_sub(dt);
+        if self.next_launch == 0 {
+            self.launch();
+            self.next_launch = self
+                .rng
+                .between(self.interval_ms as i32 / 2, self.interval_ms as i32 * 3 / 2)
+                as u32;
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        self.rockets.render(data);
+        self.sparks.render(data);
+    }
+}
+
+/// Fountain of sparks shooting up from the bottom and bouncing off the panel border
+pub struct Sparks {
+    system: ParticleSystem<96>,
+    emitter: Emitter,
+    rng: Rng,
+}
+
+impl Sparks {
+    /// `rate`: sparks per second
+    pub fn new(palette: Palette, rate: u32, seed: u32) -> Self {
+        let mut emitter = Emitter::new(0, EDGE_Y - PIXEL_UNIT, rate, palette);
+        // straight up (y points down)
+        emitter.angle = 0xc000;
+        emitter.spread = 0x2000;
+        emitter.speed = (10 * PIXEL_UNIT, 16 * PIXEL_UNIT);
+        emitter.life = (1000, 2500);
+        Sparks {
+            system: ParticleSystem::new((0, 10 * PIXEL_UNIT), 32, Boundary::Bounce(160)),
+            emitter,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Effect for Sparks {
+    fn update(&mut self, dt: u32) {
+        self.emitter.emit(dt, &mut self.system, &mut self.rng);
+        self.system.update(dt);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        self.system.render(data);
+    }
+}
+
+/// Drops falling from the top border, optionally blown sideways by `wind`
+pub struct Rain {
+    system: ParticleSystem<64>,
+    palette: Palette,
+    rate: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Rain {
+    /// `rate`: drops per second, `wind`: horizontal acceleration in pixel units per second^2
+    pub fn new(palette: Palette, rate: u32, wind: i32, seed: u32) -> Self {
+        Rain {
+            system: ParticleSystem::new((wind, 6 * PIXEL_UNIT), 64, Boundary::Kill),
+            palette,
+            rate: rate.max(1),
+            elapsed: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn drop(&mut self) {
+        let rng = &mut self.rng;
+        let x = rng.between(-PANEL_RADIUS * PIXEL_UNIT, PANEL_RADIUS * PIXEL_UNIT);
+        // top border of the panel hexagon at x (flat top, slanted sides)
+        let top = EDGE_Y.min((EDGE_Y * 256 - x.abs() * 222) / 128);
+        let life = rng.between(2000, 4000) as u16;
+        self.system.spawn(Particle {
+            x,
+            y: -top + PIXEL_UNIT / 2,
+            vx: 0,
+            vy: rng.between(2 * PIXEL_UNIT, 4 * PIXEL_UNIT),
+            life,
+            max_life: life,
+            color: self.palette.color(rng.next_u32() as u8),
+        });
+    }
+}
+
+impl Effect for Rain {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        let interval = (1000 / self.rate).max(1);
+        while self.elapsed >= interval {
+            self.elapsed -= interval;
+            self.drop();
+        }
+        self.system.update(dt);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        self.system.render(data);
+    }
+}
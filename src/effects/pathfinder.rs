@@ -0,0 +1,174 @@
+use smart_leds::RGB8;
+
+use super::{search_animation::SearchAnimation, Effect};
+use crate::{
+    color::{self, Palette},
+    hex::Cube,
+    led_index_oddr, panel_cells, random_panel_cell,
+    rng::Rng,
+    search::{Algorithm, Search},
+    FrameBuffer, NUM_LEDS,
+};
+
+// Visualized path search: walls and rough terrain are scattered over the panel, then the search
+// frontier spreads from the start (green) until it reaches the goal (red). Visited cells are
+// colored by their cost from the start, the path is traced afterwards and shown for a while
+// before a new layout is generated.
+
+/// time the finished search stays visible
+const HOLD_MS: u32 = 2000;
+
+const WALL: RGB8 = RGB8 {
+    r: 40,
+    g: 40,
+    b: 40,
+};
+const ROUGH: RGB8 = RGB8 { r: 24, g: 12, b: 0 };
+
+/// cost of entering `c`, None for walls
+fn terrain_cost(terrain: &[u8; NUM_LEDS], c: Cube) -> Option<u8> {
+    match led_index_oddr(c.into()) {
+        Some(led) if terrain[led] > 0 => Some(terrain[led]),
+        _ => None,
+    }
+}
+
+pub struct Pathfinder {
+    animation: SearchAnimation,
+    /// 0 for walls, the cost of entering the cell otherwise
+    terrain: [u8; NUM_LEDS],
+    walls: u8,
+    max_cost: u8,
+    palette: Palette,
+    step_ms: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Pathfinder {
+    /// `palette` colors visited cells by cost from the start, `step_ms`: time per expanded cell
+    pub fn new(algorithm: Algorithm, palette: Palette, step_ms: u32, seed: u32) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut pathfinder = Pathfinder {
+            animation: SearchAnimation::new(algorithm, HOLD_MS, &mut rng, |_| Cube::zero()),
+            terrain: [1; NUM_LEDS],
+            walls: 64,
+            max_cost: 1,
+            palette,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            rng,
+        };
+        pathfinder.reset();
+        pathfinder
+    }
+
+    /// share of wall cells (out of 256)
+    pub fn walls(mut self, density: u8) -> Self {
+        self.walls = density;
+        self.reset();
+        self
+    }
+
+    /// Random cell costs from 1 to `max_cost`, only Dijkstra and A* take them into account
+    pub fn terrain(mut self, max_cost: u8) -> Self {
+        self.max_cost = max_cost.max(1);
+        self.reset();
+        self
+    }
+
+    pub fn search(&self) -> &Search {
+        self.animation.search()
+    }
+
+    /// new random layout, start and goal
+    pub fn reset(&mut self) {
+        for cost in self.terrain.iter_mut() {
+            *cost = if self.rng.chance(self.walls) {
+                0
+            } else {
+                self.rng.between(1, self.max_cost as i32) as u8
+            };
+        }
+        let random_cell = |rng: &mut Rng| -> Cube { random_panel_cell(rng).into() };
+        let algorithm = self.search().algorithm();
+        self.animation = SearchAnimation::new(algorithm, HOLD_MS, &mut self.rng, random_cell);
+        for c in [self.animation.start(), self.animation.goal()].iter() {
+            if let Some(led) = led_index_oddr((*c).into()) {
+                self.terrain[led] = 1;
+            }
+        }
+        self.elapsed = 0;
+    }
+
+    fn step(&mut self) {
+        let terrain = &self.terrain;
+        if !self
+            .animation
+            .step(|c| terrain_cost(terrain, c), self.step_ms)
+        {
+            self.reset();
+        }
+    }
+}
+
+impl Effect for Pathfinder {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let search = self.search();
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            data[led] = if search.is_frontier(c) {
+                color::WHITE
+            } else if search.is_visited(c) {
+                let cost = search.cost(c).unwrap_or(0);
+                self.palette.color((cost * 8).min(255) as u8)
+            } else {
+                match self.terrain[led] {
+                    0 => WALL,
+                    1 => color::BLACK,
+                    cost => color::scale(ROUGH, (cost as u16 * 255 / self.max_cost as u16) as u8),
+                }
+            };
+        }
+        self.animation.render(data);
+    }
+}
+
+#[cfg(test)]
+mod test_pathfinder {
+    use super::*;
+    use crate::search::Status;
+
+    #[test]
+    fn test_cycle() {
+        let mut pathfinder = Pathfinder::new(Algorithm::AStar, Palette::Wheel, 10, 1)
+            .walls(48)
+            .terrain(4);
+        let (start, goal) = (pathfinder.animation.start(), pathfinder.animation.goal());
+        assert!(start.length() <= 10 && goal.length() <= 10);
+        assert_eq!(terrain_cost(&pathfinder.terrain, start), Some(1));
+        let mut data = [color::BLACK; NUM_LEDS];
+        let mut finished = false;
+        // runs through searching, tracing and holding, then starts over with a new layout
+        for _ in 0..NUM_LEDS * 2 + HOLD_MS as usize / 10 {
+            pathfinder.update(10);
+            pathfinder.render(&mut data);
+            if pathfinder.search().status() != Status::Searching {
+                finished = true;
+            } else if finished {
+                break;
+            }
+        }
+        assert!(finished);
+        assert_eq!(pathfinder.search().status(), Status::Searching);
+        assert!(pathfinder.search().visited_count() <= 1);
+    }
+}
@@ -0,0 +1,280 @@
+use smart_leds::RGB8;
+
+use super::{triangle, Direction, Effect};
+use crate::{color, color::Palette, hex::Cube, prelude::*};
+
+// Effects built on the radial structure of the panel: rings (cube distance from the center) and
+// the angle around the center (position on the ring, see Cube::angle).
+
+// position of `elapsed` within `period_ms`, a full period is 256
+fn phase(elapsed: u32, period_ms: u32) -> u8 {
+    (elapsed as u64 * 256 / period_ms as u64) as u8
+}
+
+fn ring_color(palette: &Palette, ring: i32) -> RGB8 {
+    palette.color((ring.min(PANEL_RADIUS) * 255 / PANEL_RADIUS) as u8)
+}
+
+/// Concentric waves running outwards (or inwards) from the center
+pub struct Ripples {
+    palette: Palette,
+    period_ms: u32,
+    wavelength: i32,
+    direction: Direction,
+    elapsed: u32,
+}
+
+impl Ripples {
+    /// `period_ms`: time for a wave to travel one `wavelength` (in rings)
+    pub fn new(palette: Palette, period_ms: u32, wavelength: i32, direction: Direction) -> Self {
+        Ripples {
+            palette,
+            period_ms: period_ms.max(1),
+            wavelength: wavelength.max(1),
+            direction,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Ripples {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let phase = self.direction.apply(phase(self.elapsed, self.period_ms));
+        for (led, v) in panel_cells() {
+            let ring = Cube::from(v).length();
+            let p = ((ring * 256 / self.wavelength) as u8).wrapping_sub(phase);
+            data[led] = color::scale(ring_color(&self.palette, ring), triangle(p));
+        }
+    }
+}
+
+/// Radar screen: a beam rotating around the center, leaving a fading trail
+pub struct Radar {
+    palette: Palette,
+    period_ms: u32,
+    trail: u8,
+    direction: Direction,
+    elapsed: u32,
+}
+
+impl Radar {
+    /// `period_ms`: time for one revolution, `trail`: length of the trail (full turn = 256)
+    pub fn new(palette: Palette, period_ms: u32, trail: u8, direction: Direction) -> Self {
+        Radar {
+            palette,
+            period_ms: period_ms.max(1),
+            trail: trail.max(1),
+            direction,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Radar {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let beam = phase(self.elapsed, self.period_ms);
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            // angle behind the beam
+            let behind = match self.direction {
+                Direction::Forward => beam.wrapping_sub(c.angle()),
+                Direction::Backward => c.angle().wrapping_sub(beam),
+            };
+            data[led] = if c.length() == 0 {
+                ring_color(&self.palette, 0)
+            } else if behind < self.trail {
+                let level = 255 - (behind as u32 * 255 / self.trail as u32) as u8;
+                color::scale(ring_color(&self.palette, c.length()), level)
+            } else {
+                color::BLACK
+            };
+        }
+    }
+}
+
+/// All rings breathe, neighboring rings in opposite phase
+pub struct PulsingRings {
+    palette: Palette,
+    period_ms: u32,
+    elapsed: u32,
+}
+
+impl PulsingRings {
+    /// `period_ms`: duration of one pulse
+    pub fn new(palette: Palette, period_ms: u32) -> Self {
+        PulsingRings {
+            palette,
+            period_ms: period_ms.max(1),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for PulsingRings {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let phase = phase(self.elapsed, self.period_ms);
+        for (led, v) in panel_cells() {
+            let ring = Cube::from(v).length();
+            let p = if ring % 2 == 0 {
+                phase
+            } else {
+                phase.wrapping_add(128)
+            };
+            data[led] = color::scale(ring_color(&self.palette, ring), triangle(p));
+        }
+    }
+}
+
+/// Palette spiral with `arms` arms rotating around the center
+pub struct Spiral {
+    palette: Palette,
+    period_ms: u32,
+    arms: u8,
+    twist: u8,
+    direction: Direction,
+    elapsed: u32,
+}
+
+impl Spiral {
+    /// `period_ms`: time for one revolution, `twist`: palette offset per ring
+    pub fn new(
+        palette: Palette,
+        period_ms: u32,
+        arms: u8,
+        twist: u8,
+        direction: Direction,
+    ) -> Self {
+        Spiral {
+            palette,
+            period_ms: period_ms.max(1),
+            arms: arms.max(1),
+            twist,
+            direction,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Spiral {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let phase = self.direction.apply(phase(self.elapsed, self.period_ms));
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            let index = c
+                .angle()
+                .wrapping_mul(self.arms)
+                .wrapping_add((c.length() as u8).wrapping_mul(self.twist))
+                .wrapping_add(phase);
+            data[led] = self.palette.color(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_radial {
+    use super::*;
+
+    fn frame<E: Effect>(effect: &E) -> FrameBuffer {
+        let mut data = [color::BLACK; NUM_LEDS];
+        effect.render(&mut data);
+        data
+    }
+
+    /// the pattern changes within `period_ms` and repeats after it
+    fn assert_periodic<E: Effect>(effect: &mut E, period_ms: u32) {
+        let start = frame(effect);
+        effect.update(period_ms / 2);
+        assert!(frame(effect) != start);
+        effect.update(period_ms - period_ms / 2);
+        assert!(frame(effect) == start);
+    }
+
+    fn cells() -> impl Iterator<Item = (usize, Cube)> {
+        panel_cells().map(|(led, v)| (led, Cube::from(v)))
+    }
+
+    #[test]
+    fn test_phase() {
+        assert_eq!(phase(500, 1000), 128);
+        assert_eq!(phase(30_000_000, 40_000_000), 192);
+    }
+
+    #[test]
+    fn test_ripples() {
+        let mut ripples = Ripples::new(Palette::Solid(color::WHITE), 1000, 4, Direction::Forward);
+        // rings one wavelength apart look the same
+        let data = frame(&ripples);
+        for (led, c) in cells() {
+            for (other, _) in cells().filter(|(_, d)| d.length() == c.length() + 4) {
+                assert_eq!(data[led], data[other]);
+            }
+        }
+        assert_periodic(&mut ripples, 1000);
+    }
+
+    #[test]
+    fn test_radar() {
+        let mut radar = Radar::new(Palette::Solid(color::WHITE), 2560, 64, Direction::Forward);
+        // the beam is at angle 64 after a quarter turn
+        radar.update(640);
+        let data = frame(&radar);
+        for (led, c) in cells().filter(|(_, c)| c.length() > 0) {
+            if c.angle() > 64 {
+                assert_eq!(data[led], color::BLACK);
+            }
+            // the trail fades behind the beam
+            for (other, _) in cells().filter(|(_, d)| d.length() > 0 && d.angle() < c.angle()) {
+                assert!(c.angle() > 64 || data[other].r <= data[led].r);
+            }
+        }
+        let on_beam = cells().find(|(_, c)| c.angle() == 64).unwrap().0;
+        assert_eq!(data[on_beam], color::WHITE);
+        assert_periodic(&mut radar, 2560);
+    }
+
+    #[test]
+    fn test_pulsing_rings() {
+        let mut rings = PulsingRings::new(Palette::Solid(color::WHITE), 1000);
+        let ring_level = |data: &FrameBuffer, ring: i32| {
+            let (led, _) = cells().find(|(_, c)| c.length() == ring).unwrap();
+            data[led].r
+        };
+        // neighboring rings in opposite phase
+        let data = frame(&rings);
+        assert_eq!(ring_level(&data, 2), 0);
+        assert!(ring_level(&data, 3) > 250);
+        rings.update(500);
+        let data = frame(&rings);
+        assert!(ring_level(&data, 2) > 250);
+        assert_eq!(ring_level(&data, 3), 0);
+    }
+
+    #[test]
+    fn test_spiral() {
+        let mut spiral = Spiral::new(Palette::Wheel, 1000, 2, 16, Direction::Forward);
+        // two arms: opposite cells have the same color
+        let data = frame(&spiral);
+        for (led, c) in cells() {
+            if let Some((opposite, _)) = cells().find(|(_, o)| (o.x, o.y) == (-c.x, -c.y)) {
+                assert_eq!(data[led], data[opposite]);
+            }
+        }
+        assert_periodic(&mut spiral, 1000);
+    }
+}
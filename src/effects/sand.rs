@@ -0,0 +1,484 @@
+use smart_leds::RGB8;
+
+use super::Effect;
+use crate::{
+    color::{self, Palette},
+    hex::{Cube, CubeLinedraw, CUBE_DIRECTIONS},
+    led_index_oddr,
+    math::Vec2,
+    panel_cells,
+    rng::Rng,
+    FrameBuffer, NUM_LEDS, PANEL_RADIUS,
+};
+
+// Granular simulations working directly on led indices. A neighbor table built once per effect
+// maps each led to the leds of its six neighbors, in the order of CUBE_DIRECTIONS, so the
+// simulation steps don't need any coordinate math. Downwards in the odd-r layout are the south
+// west and south east neighbors.
+
+/// neighbor table entry for cells off the panel
+const NONE: u16 = u16::MAX;
+const EAST: usize = 0;
+const WEST: usize = 3;
+const SOUTH_WEST: usize = 4;
+const SOUTH_EAST: usize = 5;
+
+type Neighbors = [[u16; 6]; NUM_LEDS];
+
+fn neighbor_table() -> Neighbors {
+    let mut table = [[NONE; 6]; NUM_LEDS];
+    for (led, v) in panel_cells() {
+        let c = Cube::from(v);
+        for (n, d) in table[led].iter_mut().zip(CUBE_DIRECTIONS.iter()) {
+            if let Some(n_led) = led_index_oddr((c + *d).into()) {
+                *n = n_led as u16;
+            }
+        }
+    }
+    table
+}
+
+/// the two directions in random order
+fn either(rng: &mut Rng, a: usize, b: usize) -> [usize; 2] {
+    if rng.bool() {
+        [a, b]
+    } else {
+        [b, a]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grain {
+    Empty,
+    /// falls down and sinks in water
+    Sand,
+    /// falls down and spreads sideways
+    Water,
+    /// never moves
+    Wall,
+}
+
+const MAX_SOURCES: usize = 4;
+/// steps with all sources blocked before the grains are cleared away
+const PATIENCE: u32 = 64;
+
+const SAND: RGB8 = RGB8 {
+    r: 200,
+    g: 150,
+    b: 40,
+};
+const WATER: RGB8 = RGB8 {
+    r: 0,
+    g: 60,
+    b: 255,
+};
+const WALL: RGB8 = RGB8 {
+    r: 60,
+    g: 60,
+    b: 60,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Source {
+    led: usize,
+    grain: Grain,
+    rate: u8,
+}
+
+/// Falling sand and water between walls, fed by sources. Once the sources are buried the sand
+/// and water are cleared and everything starts over.
+pub struct FallingSand {
+    grains: [Grain; NUM_LEDS],
+    neighbors: Neighbors,
+    /// leds from the bottom row to the top row, so grains move at most one cell per step
+    order: [u16; NUM_LEDS],
+    sources: [Source; MAX_SOURCES],
+    source_count: usize,
+    stuck: u32,
+    step_ms: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl FallingSand {
+    /// An empty world without sources, `step_ms`: time per simulation step
+    pub fn new(step_ms: u32, seed: u32) -> Self {
+        let mut order = [0; NUM_LEDS];
+        let mut n = 0;
+        for y in (-PANEL_RADIUS..=PANEL_RADIUS).rev() {
+            for (led, _) in panel_cells().filter(|(_, v)| v.y == y) {
+                order[n] = led as u16;
+                n += 1;
+            }
+        }
+        FallingSand {
+            grains: [Grain::Empty; NUM_LEDS],
+            neighbors: neighbor_table(),
+            order,
+            sources: [Source {
+                led: 0,
+                grain: Grain::Empty,
+                rate: 0,
+            }; MAX_SOURCES],
+            source_count: 0,
+            stuck: 0,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Add a source at odd-r position `v` dropping `grain` with a chance of `rate` / 256 per
+    /// step (at most 4 sources, positions off the panel are ignored)
+    pub fn source(mut self, v: Vec2, grain: Grain, rate: u8) -> Self {
+        if let Some(led) = led_index_oddr(v) {
+            if self.source_count < MAX_SOURCES {
+                self.sources[self.source_count] = Source { led, grain, rate };
+                self.source_count += 1;
+            }
+        }
+        self
+    }
+
+    /// Add a line of wall cells from `a` to `b`
+    pub fn wall(mut self, a: Vec2, b: Vec2) -> Self {
+        for c in CubeLinedraw::new(a.into(), b.into()) {
+            self.set(c.into(), Grain::Wall);
+        }
+        self.set(b, Grain::Wall);
+        self
+    }
+
+    pub fn get(&self, v: Vec2) -> Grain {
+        match led_index_oddr(v) {
+            Some(led) => self.grains[led],
+            None => Grain::Empty,
+        }
+    }
+
+    pub fn set(&mut self, v: Vec2, grain: Grain) {
+        if let Some(led) = led_index_oddr(v) {
+            self.grains[led] = grain;
+        }
+    }
+
+    /// remove all sand and water, the walls stay
+    pub fn clear(&mut self) {
+        for grain in self.grains.iter_mut() {
+            if *grain != Grain::Wall {
+                *grain = Grain::Empty;
+            }
+        }
+        self.stuck = 0;
+    }
+
+    /// number of cells with `grain`
+    pub fn count(&self, grain: Grain) -> usize {
+        self.grains.iter().filter(|g| **g == grain).count()
+    }
+
+    fn feed(&mut self) {
+        if self.source_count == 0 {
+            return;
+        }
+        let mut blocked = true;
+        for source in self.sources[..self.source_count].iter() {
+            if self.grains[source.led] == Grain::Empty {
+                blocked = false;
+                if self.rng.chance(source.rate) {
+                    self.grains[source.led] = source.grain;
+                }
+            }
+        }
+        self.stuck = if blocked { self.stuck + 1 } else { 0 };
+        if self.stuck > PATIENCE {
+            self.clear();
+        }
+    }
+
+    /// empty (or, for sand, water) neighbor of `led` in one of `dirs`
+    fn target(&self, led: usize, dirs: [usize; 2], sinks: bool) -> Option<usize> {
+        dirs.iter()
+            .map(|d| self.neighbors[led][*d])
+            .filter(|n| *n != NONE)
+            .map(|n| n as usize)
+            .find(|n| match self.grains[*n] {
+                Grain::Empty => true,
+                Grain::Water => sinks,
+                _ => false,
+            })
+    }
+
+    /// advance the simulation by one step
+    pub fn step(&mut self) {
+        self.feed();
+        let mut moved = [false; NUM_LEDS];
+        for i in 0..NUM_LEDS {
+            let led = self.order[i] as usize;
+            if moved[led] {
+                continue;
+            }
+            let down = either(&mut self.rng, SOUTH_WEST, SOUTH_EAST);
+            let target = match self.grains[led] {
+                Grain::Sand => self.target(led, down, true),
+                Grain::Water => {
+                    let side = either(&mut self.rng, EAST, WEST);
+                    self.target(led, down, false)
+                        .or_else(|| self.target(led, side, false))
+                }
+                _ => None,
+            };
+            if let Some(target) = target {
+                self.grains.swap(led, target);
+                moved[target] = true;
+            }
+        }
+    }
+}
+
+impl Effect for FallingSand {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        for (led, grain) in self.grains.iter().enumerate() {
+            data[led] = match grain {
+                Grain::Empty => color::BLACK,
+                Grain::Sand => SAND,
+                Grain::Water => WATER,
+                Grain::Wall => WALL,
+            };
+        }
+    }
+}
+
+/// cells topple at this height
+const CRITICAL: u8 = 6;
+
+/// Abelian sandpile: grains are dropped one at a time, a cell with six or more grains topples
+/// and passes one grain to each neighbor, grains falling off the panel are lost. Cells are
+/// colored by height, toppling cells white.
+pub struct Sandpile {
+    heights: [u8; NUM_LEDS],
+    neighbors: Neighbors,
+    /// led grains are dropped on, random if None
+    drop: Option<usize>,
+    palette: Palette,
+    lost: u32,
+    step_ms: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Sandpile {
+    /// Drops grains in the center, `step_ms`: time per drop or toppling round
+    pub fn new(palette: Palette, step_ms: u32, seed: u32) -> Self {
+        Sandpile {
+            heights: [0; NUM_LEDS],
+            neighbors: neighbor_table(),
+            drop: led_index_oddr(Vec2::new(0, 0)),
+            palette,
+            lost: 0,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// where grains are dropped: on odd-r position `v`, or on random cells for None
+    pub fn drop_at(mut self, v: Option<Vec2>) -> Self {
+        self.drop = v.and_then(led_index_oddr);
+        self
+    }
+
+    pub fn height(&self, v: Vec2) -> u8 {
+        match led_index_oddr(v) {
+            Some(led) => self.heights[led],
+            None => 0,
+        }
+    }
+
+    /// add `n` grains at `v` without toppling
+    pub fn add(&mut self, v: Vec2, n: u8) {
+        if let Some(led) = led_index_oddr(v) {
+            self.heights[led] = self.heights[led].saturating_add(n);
+        }
+    }
+
+    /// grains on the panel
+    pub fn grains(&self) -> u32 {
+        self.heights.iter().map(|h| *h as u32).sum()
+    }
+
+    /// grains that fell off the panel
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.heights.iter().all(|h| *h < CRITICAL)
+    }
+
+    /// Topple every critical cell once, returns the number of toppled cells
+    pub fn topple(&mut self) -> usize {
+        let mut toppled = 0;
+        for led in 0..NUM_LEDS {
+            if self.heights[led] < CRITICAL {
+                continue;
+            }
+            self.heights[led] -= CRITICAL;
+            toppled += 1;
+            for n in self.neighbors[led].iter() {
+                if *n == NONE {
+                    self.lost += 1;
+                } else {
+                    let n = *n as usize;
+                    self.heights[n] = self.heights[n].saturating_add(1);
+                }
+            }
+        }
+        toppled
+    }
+
+    /// topple until no cell is critical
+    pub fn stabilize(&mut self) {
+        while self.topple() > 0 {}
+    }
+
+    /// one toppling round, or a new grain if the pile is stable
+    pub fn step(&mut self) {
+        if self.topple() > 0 {
+            return;
+        }
+        let led = match self.drop {
+            Some(led) => led,
+            None => self.rng.range(NUM_LEDS as u32) as usize,
+        };
+        self.heights[led] = self.heights[led].saturating_add(1);
+    }
+}
+
+impl Effect for Sandpile {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        for (led, height) in self.heights.iter().enumerate() {
+            data[led] = match *height {
+                0 => color::BLACK,
+                h if h < CRITICAL => self.palette.color((h - 1) * 51),
+                _ => color::WHITE,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sand {
+    use super::*;
+
+    #[test]
+    fn test_neighbors() {
+        let table = neighbor_table();
+        let center = led_index_oddr(Vec2::new(0, 0)).unwrap();
+        // straight down in odd-r is alternately south east and south west
+        assert_eq!(
+            table[center][SOUTH_EAST] as usize,
+            led_index_oddr(Vec2::new(0, 1)).unwrap()
+        );
+        let odd = led_index_oddr(Vec2::new(0, 1)).unwrap();
+        assert_eq!(
+            table[odd][SOUTH_WEST] as usize,
+            led_index_oddr(Vec2::new(0, 2)).unwrap()
+        );
+        let edges = table.iter().flatten().filter(|n| **n == NONE).count();
+        assert!(edges > 0 && edges < NUM_LEDS);
+    }
+
+    #[test]
+    fn test_falling() {
+        let floor = 3;
+        let mut sand = FallingSand::new(10, 1).wall(Vec2::new(-5, floor), Vec2::new(5, floor));
+        assert_eq!(sand.get(Vec2::new(0, floor)), Grain::Wall);
+        sand.set(Vec2::new(0, -5), Grain::Sand);
+        for _ in 0..10 {
+            sand.step();
+        }
+        // resting on the wall, or slid off the wall
+        let resting = (-5..=5).any(|x| sand.get(Vec2::new(x, floor - 1)) == Grain::Sand);
+        assert!(resting);
+        assert_eq!(sand.count(Grain::Sand), 1);
+        // sand sinks below water
+        let mut sand = FallingSand::new(10, 1).wall(Vec2::new(-5, floor), Vec2::new(5, floor));
+        sand.set(Vec2::new(0, floor - 1), Grain::Water);
+        sand.set(Vec2::new(0, floor - 2), Grain::Sand);
+        sand.set(Vec2::new(1, floor - 1), Grain::Wall);
+        sand.set(Vec2::new(-1, floor - 1), Grain::Wall);
+        sand.step();
+        let below = sand.get(Vec2::new(0, floor - 1));
+        let water = sand.count(Grain::Water);
+        assert_eq!((below, water), (Grain::Sand, 1));
+    }
+
+    #[test]
+    fn test_sources() {
+        let mut sand = FallingSand::new(10, 1)
+            .source(Vec2::new(-2, -8), Grain::Sand, 255)
+            .source(Vec2::new(2, -8), Grain::Water, 255);
+        sand.update(200);
+        assert!(sand.count(Grain::Sand) > 10 && sand.count(Grain::Water) > 10);
+        let mut data = [color::BLACK; NUM_LEDS];
+        sand.render(&mut data);
+        assert!(data.contains(&SAND) && data.contains(&WATER));
+        // eventually the panel fills up and is cleared
+        let mut cleared = false;
+        for _ in 0..2000 {
+            let before = sand.count(Grain::Sand);
+            sand.step();
+            if sand.count(Grain::Sand) + 1 < before {
+                cleared = true;
+                break;
+            }
+        }
+        assert!(cleared);
+    }
+
+    #[test]
+    fn test_sandpile() {
+        let mut pile = Sandpile::new(Palette::Wheel, 10, 1);
+        pile.add(Vec2::new(0, 0), 6);
+        assert!(!pile.is_stable());
+        assert_eq!(pile.topple(), 1);
+        assert_eq!(pile.height(Vec2::new(0, 0)), 0);
+        assert_eq!(pile.height(Vec2::new(1, 0)), 1);
+        // the same final state for every order of adding grains (abelian), no grain lost on
+        // the way, except for those falling off the panel
+        let mut a = Sandpile::new(Palette::Wheel, 10, 1);
+        let mut b = Sandpile::new(Palette::Wheel, 10, 1);
+        for i in 0..200 {
+            a.add(Vec2::new(i % 3, 0), 1);
+            a.stabilize();
+        }
+        for x in (0..3).rev() {
+            b.add(Vec2::new(x, 0), 67 - (x == 2) as u8);
+        }
+        b.stabilize();
+        assert!(a.is_stable());
+        assert!(a.heights[..] == b.heights[..]);
+        assert_eq!(a.grains() + a.lost(), 200);
+        // the effect drops a grain per step
+        let mut pile = Sandpile::new(Palette::Wheel, 10, 1).drop_at(None);
+        pile.update(100);
+        assert_eq!(pile.grains(), 10);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    color,
+    hex::Cube,
+    led_index_oddr,
+    rng::Rng,
+    search::{Algorithm, Search, Status},
+    FrameBuffer,
+};
+
+// The animated search shared by `Pathfinder` and `Labyrinth`. The effects draw the cells by their
+// search state themselves, this adds the path and the start and goal on top.
+
+/// minimum distance between start and goal, if random cells this far apart are found
+const MIN_DISTANCE: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Searching,
+    /// number of path cells shown
+    Tracing(usize),
+    Holding(u32),
+}
+
+/// A search between two cells played back one step at a time: the search expands, the path is
+/// traced from the start and the result is held for a while.
+pub(super) struct SearchAnimation {
+    search: Search,
+    start: Cube,
+    goal: Cube,
+    hold_ms: u32,
+    phase: Phase,
+}
+
+impl SearchAnimation {
+    /// Start and goal are picked by `random_cell`, retrying for a goal at least MIN_DISTANCE
+    /// away from the start
+    pub(super) fn new<F: FnMut(&mut Rng) -> Cube>(
+        algorithm: Algorithm,
+        hold_ms: u32,
+        rng: &mut Rng,
+        mut random_cell: F,
+    ) -> Self {
+        let start = random_cell(rng);
+        let mut goal = random_cell(rng);
+        for _ in 0..100 {
+            if (goal - start).length() >= MIN_DISTANCE {
+                break;
+            }
+            goal = random_cell(rng);
+        }
+        SearchAnimation {
+            search: Search::new(algorithm, start, goal),
+            start,
+            goal,
+            hold_ms,
+            phase: Phase::Searching,
+        }
+    }
+
+    pub(super) fn search(&self) -> &Search {
+        &self.search
+    }
+
+    pub(super) fn start(&self) -> Cube {
+        self.start
+    }
+
+    pub(super) fn goal(&self) -> Cube {
+        self.goal
+    }
+
+    /// Advance by one step taking `step_ms`, `cost` as for `Search::step`. Returns false once
+    /// the result was held long enough.
+    pub(super) fn step<F: Fn(Cube) -> Option<u8>>(&mut self, cost: F, step_ms: u32) -> bool {
+        self.phase = match self.phase {
+            Phase::Searching => match self.search.step(cost) {
+                Status::Searching => Phase::Searching,
+                Status::Found => Phase::Tracing(0),
+                Status::Unreachable => Phase::Holding(0),
+            },
+            Phase::Tracing(shown) if shown < self.search.path().count() => {
+                Phase::Tracing(shown + 1)
+            }
+            Phase::Tracing(_) => Phase::Holding(0),
+            Phase::Holding(held) if held + step_ms < self.hold_ms => Phase::Holding(held + step_ms),
+            Phase::Holding(_) => return false,
+        };
+        true
+    }
+
+    /// Draw the traced part of the path, then start (green) and goal (red) on top
+    pub(super) fn render(&self, data: &mut FrameBuffer) {
+        let len = self.search.path().count();
+        let shown = match self.phase {
+            Phase::Searching => 0,
+            Phase::Tracing(shown) => shown,
+            Phase::Holding(_) => len,
+        };
+        // the path runs from the goal back to the start
+        for c in self.search.path().skip(len - shown) {
+            if let Some(led) = led_index_oddr(c.into()) {
+                data[led] = color::YELLOW;
+            }
+        }
+        for (c, color) in [(self.start, color::GREEN), (self.goal, color::RED)].iter() {
+            if let Some(led) = led_index_oddr((*c).into()) {
+                data[led] = *color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_search_animation {
+    use super::*;
+    use crate::NUM_LEDS;
+
+    fn open(_: Cube) -> Option<u8> {
+        Some(1)
+    }
+
+    #[test]
+    fn test_phases() {
+        let (start, goal) = (Cube::new(-5, 5, 0), Cube::new(5, -5, 0));
+        let cells = [start, goal];
+        let mut cells = cells.iter();
+        let mut rng = Rng::new(1);
+        let mut animation =
+            SearchAnimation::new(Algorithm::AStar, 100, &mut rng, |_| *cells.next().unwrap());
+        let yellow = |animation: &SearchAnimation| {
+            let mut data = [color::BLACK; NUM_LEDS];
+            animation.render(&mut data);
+            assert_eq!(data[led_index_oddr(start.into()).unwrap()], color::GREEN);
+            assert_eq!(data[led_index_oddr(goal.into()).unwrap()], color::RED);
+            data.iter().filter(|c| **c == color::YELLOW).count()
+        };
+        while animation.search().status() == Status::Searching {
+            assert_eq!(yellow(&animation), 0);
+            assert!(animation.step(open, 10));
+        }
+        assert_eq!(animation.search().status(), Status::Found);
+        // the path is traced one cell per step, start and goal are drawn over its ends
+        let len = animation.search().path().count();
+        assert_eq!(len, 11);
+        let mut traced = 0;
+        while yellow(&animation) < len - 2 {
+            assert!(animation.step(open, 10));
+            traced += 1;
+        }
+        assert!(traced < len);
+        // then the path stays visible for the hold time
+        let mut held = 0;
+        while animation.step(open, 10) {
+            assert_eq!(yellow(&animation), len - 2);
+            held += 10;
+        }
+        assert!((100..=130).contains(&held));
+    }
+}
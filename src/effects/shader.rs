@@ -0,0 +1,267 @@
+use smart_leds::RGB8;
+
+use super::Effect;
+use crate::{
+    color::{self, Palette},
+    fixed::{cos16, isqrt, sin16, sin8},
+    hex::{Cube, PIXEL_UNIT},
+    math::Vec2,
+    panel_cells, FrameBuffer,
+};
+
+// "Shaders": a function of (cell, time) evaluated for every led. Everything is integer / table
+// based (see fixed.rs), so there is no dependency on float precision or speed.
+
+/// A led as seen by a shader
+#[derive(Debug, Clone, Copy)]
+pub struct Cell {
+    pub led: usize,
+    pub cube: Cube,
+    /// cartesian position of the cell center relative to the panel center (x right, y down), in
+    /// units of `hex::PIXEL_UNIT`
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Cell {
+    fn new(led: usize, v: Vec2) -> Cell {
+        let cube = Cube::from(v);
+        let (x, y) = cube.to_pixel();
+        Cell { led, cube, x, y }
+    }
+}
+
+/// Evaluate `f(cell, t)` for every led of the panel
+pub fn shade<F: Fn(&Cell, u32) -> RGB8>(data: &mut FrameBuffer, t: u32, f: F) {
+    for (led, v) in panel_cells() {
+        data[led] = f(&Cell::new(led, v), t);
+    }
+}
+
+/// Effect running an arbitrary shader function, `t` is the time in ms since start
+pub struct Shader<F> {
+    f: F,
+    t: u32,
+}
+
+impl<F: Fn(&Cell, u32) -> RGB8> Shader<F> {
+    pub fn new(f: F) -> Self {
+        Shader { f, t: 0 }
+    }
+}
+
+impl<F: Fn(&Cell, u32) -> RGB8> Effect for Shader<F> {
+    fn update(&mut self, dt: u32) {
+        self.t = self.t.wrapping_add(dt);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        shade(data, self.t, &self.f);
+    }
+}
+
+// position of `elapsed` within `period_ms` as 16 bit angle
+fn phase16(elapsed: u32, period_ms: u32) -> u16 {
+    (((elapsed as u64) << 16) / period_ms as u64) as u16
+}
+
+/// Classic plasma: sum of sine waves along x, y, the diagonal and the distance from the center
+pub struct Plasma {
+    palette: Palette,
+    period_ms: u32,
+    scale: i32,
+    elapsed: u32,
+}
+
+impl Plasma {
+    /// `period_ms`: time until the pattern repeats, `scale`: spatial frequency (12 gives roughly
+    /// one wave across the panel)
+    pub fn new(palette: Palette, period_ms: u32, scale: i32) -> Self {
+        Plasma {
+            palette,
+            period_ms: period_ms.max(1),
+            scale,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Plasma {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let t = phase16(self.elapsed, self.period_ms);
+        let k = self.scale;
+        shade(data, 0, |c, _| {
+            let r = isqrt((c.x * c.x + c.y * c.y) as u32) as i32;
+            let v = sin16(((c.x * k) as u16).wrapping_add(t)) as i32
+                + sin16(((c.y * k) as u16).wrapping_sub(t.wrapping_mul(2))) as i32
+                + sin16((((c.x + c.y) * k / 2) as u16).wrapping_add(t.wrapping_mul(3))) as i32
+                + sin16(((r * k) as u16).wrapping_sub(t)) as i32;
+            self.palette.color(((v + 4 * 32768) >> 10) as u8)
+        });
+    }
+}
+
+/// Interference pattern of circular waves emitted by three sources moving around the center
+pub struct Interference {
+    palette: Palette,
+    period_ms: u32,
+    wavelength: i32,
+    elapsed: u32,
+}
+
+impl Interference {
+    /// `period_ms`: time until the source movement repeats, `wavelength`: in cells
+    pub fn new(palette: Palette, period_ms: u32, wavelength: i32) -> Self {
+        Interference {
+            palette,
+            period_ms: period_ms.max(1),
+            wavelength: wavelength.max(1),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for Interference {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let t = phase16(self.elapsed, self.period_ms);
+        // sources on lissajous curves with a radius of ~6 cells
+        let radius = 6 * PIXEL_UNIT;
+        let mut sources = [(0i32, 0i32); 3];
+        for (i, s) in sources.iter_mut().enumerate() {
+            let i = i as u16;
+            let a = t.wrapping_mul(i + 1).wrapping_add(i * 21845);
+            let b = t.wrapping_mul(3 - i).wrapping_add(i * 10923);
+            *s = (
+                cos16(a) as i32 * radius / 32768,
+                sin16(b) as i32 * radius / 32768,
+            );
+        }
+        // waves travel outwards by 8 wavelengths per period
+        let wave_t = t.wrapping_mul(8);
+        let wavelength = self.wavelength;
+        shade(data, 0, |c, _| {
+            let v: i32 = sources
+                .iter()
+                .map(|(x, y)| {
+                    let dx = c.x - x;
+                    let dy = c.y - y;
+                    let d = isqrt((dx * dx + dy * dy) as u32) as i32;
+                    sin16(((d * 256 / wavelength) as u16).wrapping_sub(wave_t)) as i32
+                })
+                .sum();
+            self.palette
+                .color(((v + 3 * 32768) * 255 / (6 * 32768)) as u8)
+        });
+    }
+}
+
+/// Palette bands drifting diagonally, bent by a slow wave and modulated in brightness
+pub struct ColorWaves {
+    palette: Palette,
+    period_ms: u32,
+    elapsed: u32,
+}
+
+impl ColorWaves {
+    /// `period_ms`: time for a band to run through the whole palette
+    pub fn new(palette: Palette, period_ms: u32) -> Self {
+        ColorWaves {
+            palette,
+            period_ms: period_ms.max(1),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Effect for ColorWaves {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = (self.elapsed + dt) % self.period_ms;
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let t = (phase16(self.elapsed, self.period_ms) >> 8) as u8;
+        shade(data, 0, |c, _| {
+            let bend = sin8(((c.y / 32) as u8).wrapping_add(t)) / 4;
+            let index = ((c.x + c.y) / 48) as u8;
+            let index = index.wrapping_add(bend).wrapping_sub(t);
+            let wave = sin8((((c.x - c.y) / 24) as u8).wrapping_add(t.wrapping_mul(2)));
+            let level = 128 + wave / 2;
+            color::scale(self.palette.color(index), level)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_shader {
+    use super::*;
+    use crate::NUM_LEDS;
+
+    /// frames at the start, a third into `period_ms` and after a full period
+    fn frames<E: Effect>(effect: &mut E, period_ms: u32) -> [FrameBuffer; 3] {
+        let mut frames = [[color::BLACK; NUM_LEDS]; 3];
+        effect.render(&mut frames[0]);
+        effect.update(period_ms / 3);
+        effect.render(&mut frames[1]);
+        effect.update(period_ms - period_ms / 3);
+        effect.render(&mut frames[2]);
+        frames
+    }
+
+    /// the pattern moves, repeats after the period and isn't uniform
+    fn assert_periodic<E: Effect>(mut effect: E, period_ms: u32) {
+        let frames = frames(&mut effect, period_ms);
+        assert!(frames[0] != frames[1]);
+        assert!(frames[0] == frames[2]);
+        assert!(frames[0].iter().any(|c| *c != frames[0][0]));
+    }
+
+    #[test]
+    fn test_shader() {
+        let (center, _) = panel_cells().find(|(_, v)| *v == Vec2::new(0, 0)).unwrap();
+        let mut shader = Shader::new(|c: &Cell, t| {
+            if c.x == 0 && c.y == 0 || c.led == t as usize / 10 {
+                color::WHITE
+            } else {
+                color::BLACK
+            }
+        });
+        let mut data = [color::BLACK; NUM_LEDS];
+        shader.render(&mut data);
+        assert_eq!(data.iter().filter(|c| **c == color::WHITE).count(), 2);
+        assert_eq!(data[center], color::WHITE);
+        assert_eq!(data[0], color::WHITE);
+        // the time is passed on to the shader
+        shader.update(30);
+        shader.render(&mut data);
+        assert_eq!(data[3], color::WHITE);
+        assert_eq!(data[0], color::BLACK);
+    }
+
+    #[test]
+    fn test_plasma() {
+        assert_periodic(Plasma::new(Palette::Wheel, 3000, 12), 3000);
+    }
+
+    #[test]
+    fn test_interference() {
+        assert_periodic(Interference::new(Palette::Wheel, 6000, 4), 6000);
+    }
+
+    #[test]
+    fn test_color_waves() {
+        let waves = ColorWaves::new(Palette::Wheel, 2000);
+        let mut data = [color::BLACK; NUM_LEDS];
+        waves.render(&mut data);
+        // the brightness modulation never goes down to black
+        assert!(data.iter().all(|c| *c != color::BLACK));
+        assert_periodic(waves, 2000);
+    }
+}
@@ -0,0 +1,107 @@
+use super::Effect;
+use crate::{
+    audio::{log2_16, Analyzer, PeakMeter},
+    color::{self, Palette},
+    hex::Cube,
+    prelude::*,
+};
+
+// Audio reactive spectrum display. The firmware reads a block of microphone samples (ADC) and
+// passes it to `Spectrum::input` before every update.
+
+/// samples per analyzed block
+pub const BLOCK_SIZE: usize = 128;
+const BANDS: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// one band per ring (low frequencies in the center), lit clockwise like a round bar graph
+    Rings,
+    /// one band per 30 degree sector, drawn as bar from the center outwards
+    Sectors,
+}
+
+pub struct Spectrum {
+    analyzer: Analyzer<BLOCK_SIZE>,
+    meter: PeakMeter<BANDS>,
+    palette: Palette,
+    layout: Layout,
+    floor: u32,
+}
+
+impl Spectrum {
+    /// `floor`: magnitude (see `audio::Analyzer::magnitude`) that is considered silence, every
+    /// doubling above it adds 1/8 to the level
+    pub fn new(palette: Palette, layout: Layout, floor: u32) -> Self {
+        Spectrum {
+            analyzer: Analyzer::new(),
+            meter: PeakMeter::new(400, 400),
+            palette,
+            layout,
+            floor: floor.max(1),
+        }
+    }
+
+    fn bands(&self) -> usize {
+        match self.layout {
+            Layout::Rings => PANEL_RADIUS as usize + 1,
+            Layout::Sectors => BANDS,
+        }
+    }
+
+    /// analyze a block of samples (up to BLOCK_SIZE are used)
+    pub fn input(&mut self, samples: &[i16]) {
+        self.analyzer.process(samples);
+        let mut magnitudes = [0; BANDS];
+        let bands = self.bands();
+        self.analyzer.bands(&mut magnitudes[..bands]);
+        let floor = log2_16(self.floor);
+        let mut levels = [0; BANDS];
+        for (l, m) in levels.iter_mut().zip(magnitudes.iter()) {
+            // 16 steps per doubling -> 8 doublings for the full range
+            *l = ((log2_16(*m).saturating_sub(floor)) * 2).min(255) as u8;
+        }
+        self.meter.input(&levels[..bands]);
+    }
+
+    /// current level of every band (0..=255), e.g. for other effects reacting to the music
+    pub fn levels(&self) -> &[u8] {
+        &self.meter.levels[..self.bands()]
+    }
+}
+
+impl Effect for Spectrum {
+    fn update(&mut self, dt: u32) {
+        self.meter.update(dt);
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        let bands = self.bands();
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            let ring = c.length();
+            // band, position of the cell along the bar (0..=255) and the size of a cell
+            let (band, pos, step) = match self.layout {
+                Layout::Rings => (ring as usize, c.angle(), (256 / (6 * ring.max(1))) as u8),
+                Layout::Sectors => (
+                    c.angle() as usize * BANDS / 256,
+                    (ring * 255 / PANEL_RADIUS) as u8,
+                    (256 / PANEL_RADIUS) as u8 + 1,
+                ),
+            };
+            let level = self.meter.levels[band];
+            let peak = self.meter.peaks[band];
+            let base = self.palette.color((band * 255 / (bands - 1)) as u8);
+            data[led] = if ring == 0 && self.layout == Layout::Rings {
+                // a single cell, it shows the level as brightness
+                color::scale(base, level)
+            } else if pos < level {
+                base
+            } else if peak > 0 && pos <= peak && peak - pos < step {
+                color::WHITE
+            } else {
+                color::BLACK
+            };
+        }
+    }
+}
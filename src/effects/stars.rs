@@ -0,0 +1,242 @@
+use smart_leds::RGB8;
+
+use super::Effect;
+use crate::{
+    color,
+    fixed::sin16,
+    hex::{Cube, CUBE_DIRECTIONS},
+    led_index_oddr,
+    rng::Rng,
+    FrameBuffer, NUM_LEDS, PANEL_RADIUS,
+};
+
+// Night sky effects. All randomness comes from the seeded generator, so the same seed always
+// produces the same frames.
+
+const MAX_STARS: usize = 64;
+
+const WARM: RGB8 = RGB8 {
+    r: 255,
+    g: 160,
+    b: 80,
+};
+const COOL: RGB8 = RGB8 {
+    r: 160,
+    g: 190,
+    b: 255,
+};
+
+/// star color from reddish (0) over white to bluish (255)
+fn temperature(t: u8) -> RGB8 {
+    if t < 128 {
+        color::blend(WARM, color::WHITE, t * 2)
+    } else {
+        color::blend(color::WHITE, COOL, (t - 128) * 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Twinkler {
+    led: usize,
+    color: RGB8,
+    /// position within one fade in / fade out, full turn = 65536
+    phase: u32,
+    /// phase advance per ms
+    speed: u32,
+}
+
+/// Random cells fading in and out independently, with random color temperature and speed
+pub struct Twinkle {
+    stars: [Twinkler; MAX_STARS],
+    count: usize,
+    period_ms: u32,
+    rng: Rng,
+}
+
+impl Twinkle {
+    /// `count`: number of stars at the same time (at most 64), `period_ms`: average time for a
+    /// star to fade in and out
+    pub fn new(count: usize, period_ms: u32, seed: u32) -> Self {
+        let mut twinkle = Twinkle {
+            stars: [Twinkler {
+                led: 0,
+                color: color::BLACK,
+                phase: 0,
+                speed: 0,
+            }; MAX_STARS],
+            count: count.min(MAX_STARS),
+            period_ms: period_ms.max(2),
+            rng: Rng::new(seed),
+        };
+        for i in 0..twinkle.count {
+            twinkle.respawn(i);
+            // don't start all in sync
+            twinkle.stars[i].phase = twinkle.rng.range(65536);
+        }
+        twinkle
+    }
+
+    fn respawn(&mut self, i: usize) {
+        let rng = &mut self.rng;
+        let period = self.period_ms / 2 + rng.range(self.period_ms);
+        self.stars[i] = Twinkler {
+            led: rng.range(NUM_LEDS as u32) as usize,
+            color: temperature(rng.next_u32() as u8),
+            phase: 0,
+            speed: (65536 / period).max(1),
+        };
+    }
+}
+
+impl Effect for Twinkle {
+    fn update(&mut self, dt: u32) {
+        for i in 0..self.count {
+            let star = &mut self.stars[i];
+            star.phase += star.speed * dt;
+            if star.phase >= 65536 {
+                self.respawn(i);
+            }
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        for star in self.stars[..self.count].iter() {
+            // half sine wave, squared for a sharper flash
+            let level = (sin16((star.phase / 2) as u16) as u32) >> 7;
+            let level = (level * level) >> 8;
+            data[star.led] = color::add(data[star.led], color::scale(star.color, level as u8));
+        }
+    }
+}
+
+/// depth of a starfield star when it reaches the panel border
+const DEPTH: i32 = 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct Streamer {
+    /// end of the ray in pixel coordinates
+    target: (i32, i32),
+    /// distance travelled along the ray, DEPTH at the panel border
+    depth: i32,
+    color: RGB8,
+}
+
+/// Stars streaming out of the panel center along hex rays, speeding up and getting brighter on
+/// their way out
+pub struct Starfield {
+    stars: [Streamer; MAX_STARS],
+    count: usize,
+    speed: u32,
+    rng: Rng,
+}
+
+impl Starfield {
+    /// `count`: number of stars (at most 64), `speed`: relative growth of the distance from the
+    /// center per second, in 1/16 (a star needs about 24 / `speed` seconds to cross the panel)
+    pub fn new(count: usize, speed: u32, seed: u32) -> Self {
+        let mut starfield = Starfield {
+            stars: [Streamer {
+                target: (0, 0),
+                depth: 0,
+                color: color::BLACK,
+            }; MAX_STARS],
+            count: count.min(MAX_STARS),
+            speed,
+            rng: Rng::new(seed),
+        };
+        for i in 0..starfield.count {
+            starfield.respawn(i);
+            starfield.stars[i].depth = starfield.rng.range(DEPTH as u32) as i32;
+        }
+        starfield
+    }
+
+    fn respawn(&mut self, i: usize) {
+        // ray towards a random cell of the outermost ring
+        let radius = PANEL_RADIUS;
+        let k = self.rng.range(6 * radius as u32) as i32;
+        let side = (k / radius) as usize;
+        let corner = CUBE_DIRECTIONS[side];
+        let step = CUBE_DIRECTIONS[(side + 1) % 6] - corner;
+        let target = corner * radius + step * (k % radius);
+        self.stars[i] = Streamer {
+            target: target.to_pixel(),
+            depth: 0,
+            color: temperature(self.rng.next_u32() as u8),
+        };
+    }
+}
+
+impl Effect for Starfield {
+    fn update(&mut self, dt: u32) {
+        for i in 0..self.count {
+            let star = &mut self.stars[i];
+            // perspective: the farther out, the faster
+            let v = (self.speed * dt) as i32 * (star.depth + DEPTH / 8) / (16 * 1000);
+            star.depth += v.max(1);
+            if star.depth > DEPTH {
+                self.respawn(i);
+            }
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        for star in self.stars[..self.count].iter() {
+            let x = star.target.0 * star.depth / DEPTH;
+            let y = star.target.1 * star.depth / DEPTH;
+            if let Some(led) = led_index_oddr(Cube::from_pixel(x, y).into()) {
+                let level = (star.depth * 255 / DEPTH) as u8;
+                data[led] = color::add(data[led], color::scale(star.color, level));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_stars {
+    use super::*;
+
+    fn frames<E: Effect>(effect: &mut E, n: usize) -> [FrameBuffer; 4] {
+        let mut out = [[color::BLACK; NUM_LEDS]; 4];
+        for frame in out.iter_mut() {
+            for _ in 0..n {
+                effect.update(10);
+            }
+            effect.render(frame);
+        }
+        out
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let a = frames(&mut Twinkle::new(40, 2000, 7), 50);
+        assert_eq!(a, frames(&mut Twinkle::new(40, 2000, 7), 50));
+        assert_ne!(a, frames(&mut Twinkle::new(40, 2000, 8), 50));
+
+        let a = frames(&mut Starfield::new(40, 8, 7), 50);
+        assert_eq!(a, frames(&mut Starfield::new(40, 8, 7), 50));
+        assert_ne!(a, frames(&mut Starfield::new(40, 8, 8), 50));
+    }
+
+    #[test]
+    fn test_star_count() {
+        let mut twinkle = Twinkle::new(20, 1000, 1);
+        let mut data = [color::BLACK; NUM_LEDS];
+        for _ in 0..100 {
+            twinkle.update(10);
+            twinkle.render(&mut data);
+            assert!(data.iter().filter(|c| **c != color::BLACK).count() <= 20);
+        }
+    }
+
+    #[test]
+    fn test_rays_end_on_panel_border() {
+        let starfield = Starfield::new(64, 8, 3);
+        for star in starfield.stars.iter() {
+            let (x, y) = star.target;
+            assert_eq!(Cube::from_pixel(x, y).length(), PANEL_RADIUS);
+        }
+    }
+}
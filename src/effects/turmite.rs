@@ -0,0 +1,243 @@
+use smart_leds::RGB8;
+
+use super::Effect;
+use crate::{
+    color::{self, Palette},
+    hex::{Cube, CUBE_DIRECTIONS},
+    led_index_oddr, random_panel_cell,
+    rng::Rng,
+    FrameBuffer, NUM_LEDS,
+};
+
+// Langton's ant on the hex grid, generalized to more colors (turmites in the wider sense). Every
+// cell has a color state. An ant turns according to the state of the cell it is on, advances the
+// state of the cell to the next color and moves on. The world is the panel, an ant that would
+// walk off the panel turns around instead.
+
+const MAX_STATES: usize = 16;
+const MAX_ANTS: usize = 8;
+
+/// Turn of an ant, in steps of 60 degrees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    /// N: keep going
+    None,
+    /// R1
+    Right60,
+    /// R2
+    Right120,
+    /// U
+    Back,
+    /// L2
+    Left120,
+    /// L1
+    Left60,
+}
+
+impl Turn {
+    /// change of the index into CUBE_DIRECTIONS (which run counter clockwise)
+    fn steps(&self) -> usize {
+        match self {
+            Turn::None => 0,
+            Turn::Left60 => 1,
+            Turn::Left120 => 2,
+            Turn::Back => 3,
+            Turn::Right120 => 4,
+            Turn::Right60 => 5,
+        }
+    }
+}
+
+/// Turn for every color state, the number of turns is the number of states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    turns: [Turn; MAX_STATES],
+    len: usize,
+}
+
+impl Rule {
+    /// Parse a rule string of the turns N, R1, R2, U, L2 and L1 (R and L are short for R1 and L1),
+    /// one per state, e.g. "L1R1" or "L2 N N L1 L2 L1". Returns None for invalid strings, for
+    /// less than two or more than 16 states.
+    pub fn parse(s: &str) -> Option<Rule> {
+        let mut rule = Rule {
+            turns: [Turn::None; MAX_STATES],
+            len: 0,
+        };
+        let mut chars = s.chars().filter(|c| !c.is_whitespace()).peekable();
+        while let Some(c) = chars.next() {
+            let amount = match chars.peek() {
+                Some('1') | Some('2') => chars.next(),
+                _ => None,
+            };
+            let turn = match (c, amount) {
+                ('N', None) => Turn::None,
+                ('U', None) => Turn::Back,
+                ('R', None) | ('R', Some('1')) => Turn::Right60,
+                ('R', Some('2')) => Turn::Right120,
+                ('L', None) | ('L', Some('1')) => Turn::Left60,
+                ('L', Some('2')) => Turn::Left120,
+                _ => return None,
+            };
+            if rule.len == MAX_STATES {
+                return None;
+            }
+            rule.turns[rule.len] = turn;
+            rule.len += 1;
+        }
+        if rule.len < 2 {
+            return None;
+        }
+        Some(rule)
+    }
+
+    /// number of color states
+    pub fn states(&self) -> usize {
+        self.len
+    }
+
+    pub fn turn(&self, state: u8) -> Turn {
+        self.turns[state as usize % self.len]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ant {
+    cell: Cube,
+    /// index into CUBE_DIRECTIONS
+    dir: usize,
+}
+
+/// One or more ants walking around the panel, cells are colored by their state (state 0 is
+/// black, the others are spread over the palette)
+pub struct Turmites {
+    rule: Rule,
+    states: [u8; NUM_LEDS],
+    ants: [Ant; MAX_ANTS],
+    count: usize,
+    palette: Palette,
+    step_ms: u32,
+    elapsed: u32,
+    steps: u32,
+}
+
+impl Turmites {
+    /// `ants`: number of ants (at most 8), the first one starts in the center, the others on
+    /// random cells, `step_ms`: time per step of all ants
+    pub fn new(rule: Rule, ants: usize, palette: Palette, step_ms: u32, seed: u32) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut turmites = Turmites {
+            rule,
+            states: [0; NUM_LEDS],
+            ants: [Ant {
+                cell: Cube::zero(),
+                dir: 0,
+            }; MAX_ANTS],
+            count: ants.min(MAX_ANTS),
+            palette,
+            step_ms: step_ms.max(1),
+            elapsed: 0,
+            steps: 0,
+        };
+        // the first ant starts in the center
+        for (i, ant) in turmites.ants[..turmites.count].iter_mut().enumerate() {
+            if i > 0 {
+                ant.cell = random_panel_cell(&mut rng).into();
+            }
+            ant.dir = rng.range(6) as usize;
+        }
+        turmites
+    }
+
+    /// number of steps since the start
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// advance all ants by one cell
+    pub fn step(&mut self) {
+        for ant in self.ants[..self.count].iter_mut() {
+            let led = match led_index_oddr(ant.cell.into()) {
+                Some(led) => led,
+                None => continue,
+            };
+            let state = self.states[led];
+            ant.dir = (ant.dir + self.rule.turn(state).steps()) % 6;
+            self.states[led] = ((state as usize + 1) % self.rule.states()) as u8;
+            let next = ant.cell + CUBE_DIRECTIONS[ant.dir];
+            if led_index_oddr(next.into()).is_some() {
+                ant.cell = next;
+            } else {
+                ant.dir = (ant.dir + 3) % 6;
+            }
+        }
+        self.steps = self.steps.wrapping_add(1);
+    }
+
+    fn state_color(&self, state: u8) -> RGB8 {
+        if state == 0 {
+            color::BLACK
+        } else {
+            let index = (state as usize - 1) * 255 / (self.rule.states() - 1).max(1);
+            self.palette.color(index as u8)
+        }
+    }
+}
+
+impl Effect for Turmites {
+    fn update(&mut self, dt: u32) {
+        self.elapsed += dt;
+        while self.elapsed >= self.step_ms {
+            self.elapsed -= self.step_ms;
+            self.step();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        for (led, state) in self.states.iter().enumerate() {
+            data[led] = self.state_color(*state);
+        }
+        for ant in self.ants[..self.count].iter() {
+            if let Some(led) = led_index_oddr(ant.cell.into()) {
+                data[led] = color::WHITE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_turmite {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let rule = Rule::parse("L2 N N L1 L2 L1").unwrap();
+        assert_eq!(rule.states(), 6);
+        assert_eq!(rule.turn(1), Turn::None);
+        assert_eq!(rule.turn(7), Turn::None);
+        assert_eq!(Rule::parse("RL"), Rule::parse("R1L1"));
+        assert_eq!(Rule::parse("L"), None);
+        assert_eq!(Rule::parse("L3R"), None);
+        assert_eq!(Rule::parse("LRX"), None);
+    }
+
+    #[test]
+    fn test_walk() {
+        let rule = Rule::parse("L1R1").unwrap();
+        let mut ant = Turmites::new(rule, 1, Palette::Solid(color::RED), 10, 1);
+        ant.ants[0].dir = 0;
+        ant.step();
+        // turned left on the blank center cell and moved north east, leaving the center colored
+        let center = led_index_oddr(Cube::zero().into()).unwrap();
+        assert_eq!(ant.states[center], 1);
+        let c = ant.ants[0].cell;
+        assert_eq!((c.x, c.y, c.z), (1, 0, -1));
+        // the ant never leaves the panel
+        ant.update(100_000);
+        assert_eq!(ant.steps(), 10_001);
+        assert!(led_index_oddr(ant.ants[0].cell.into()).is_some());
+        let mut data = [color::BLACK; NUM_LEDS];
+        ant.render(&mut data);
+        assert!(data.contains(&color::RED));
+    }
+}
@@ -0,0 +1,107 @@
+// Table based fixed point trigonometry. Angles are unsigned integers where a full turn wraps
+// around exactly (65536 for the 16 bit variants, 256 for the 8 bit ones), so they can be advanced
+// with wrapping arithmetic without any range checks.
+
+/// quarter sine wave, 64 steps + end point, scaled to 32767
+const SIN_QUARTER: [i16; 65] = [
+    0, 804, 1608, 2410, 3212, 4011, 4808, 5602, //
+    6393, 7179, 7962, 8739, 9512, 10278, 11039, 11793, //
+    12539, 13279, 14010, 14732, 15446, 16151, 16846, 17530, //
+    18204, 18868, 19519, 20159, 20787, 21403, 22005, 22594, //
+    23170, 23731, 24279, 24811, 25329, 25832, 26319, 26790, //
+    27245, 27683, 28105, 28510, 28898, 29268, 29621, 29956, //
+    30273, 30571, 30852, 31113, 31356, 31580, 31785, 31971, //
+    32137, 32285, 32412, 32521, 32609, 32678, 32728, 32757, //
+    32767,
+];
+
+/// sine of `angle` (full turn = 65536) in -32767..=32767, linearly interpolated from the table
+pub fn sin16(angle: u16) -> i16 {
+    let quadrant = angle >> 14;
+    let mut offset = (angle & 0x3fff) as i32;
+    if quadrant & 1 != 0 {
+        offset = 0x4000 - offset;
+    }
+    let i = (offset >> 8) as usize;
+    let frac = offset & 0xff;
+    let v = if i >= 64 {
+        SIN_QUARTER[64] as i32
+    } else {
+        let a = SIN_QUARTER[i] as i32;
+        let b = SIN_QUARTER[i + 1] as i32;
+        a + (((b - a) * frac) >> 8)
+    };
+    if quadrant >= 2 {
+        -v as i16
+    } else {
+        v as i16
+    }
+}
+
+/// cosine of `angle` (full turn = 65536) in -32767..=32767
+pub fn cos16(angle: u16) -> i16 {
+    sin16(angle.wrapping_add(0x4000))
+}
+
+/// sine of `angle` (full turn = 256) mapped to 0..=255, i.e. sin8(0) == 128
+pub fn sin8(angle: u8) -> u8 {
+    ((sin16((angle as u16) << 8) as i32 + 32768) >> 8) as u8
+}
+
+/// cosine of `angle` (full turn = 256) mapped to 0..=255
+pub fn cos8(angle: u8) -> u8 {
+    sin8(angle.wrapping_add(64))
+}
+
+/// integer square root (floor)
+pub fn isqrt(v: u32) -> u32 {
+    if v < 2 {
+        return v;
+    }
+    // Newton iteration, starting above the result
+    let mut x = v;
+    let mut y = v / 2 + (v & 1);
+    while y < x {
+        x = y;
+        y = (x + v / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod test_fixed {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_sin16() {
+        assert_eq!(sin16(0), 0);
+        assert_eq!(sin16(0x4000), 32767);
+        assert_eq!(sin16(0x8000), 0);
+        assert_eq!(sin16(0xc000), -32767);
+        assert_eq!(cos16(0), 32767);
+        // compare against libm on a coarse grid
+        for a in (0..65536).step_by(97) {
+            let exact = (a as f64 / 65536.0 * 2.0 * core::f64::consts::PI).sin() * 32767.0;
+            let v = sin16(a as u16) as f64;
+            assert!((v - exact).abs() < 40.0, "{} {} {}", a, v, exact);
+        }
+    }
+
+    #[test]
+    fn test_sin8() {
+        assert_eq!(sin8(0), 128);
+        assert_eq!(sin8(64), 255);
+        assert_eq!(sin8(192), 0);
+        assert_eq!(cos8(0), 255);
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for v in 0..10000u32 {
+            let r = isqrt(v);
+            assert!(r * r <= v && (r + 1) * (r + 1) > v);
+        }
+        assert_eq!(isqrt(u32::MAX), 65535);
+    }
+}
@@ -0,0 +1,38 @@
+use smart_leds::RGB8;
+
+use crate::{effects::clock::draw_digit, math::Vec2, FrameBuffer};
+
+// Games played with the single user button. The game logic only sees button gestures and time
+// steps, so it runs on the host with scripted input; the firmware turns the raw button state into
+// gestures with `input::Gestures`. Games are effects, so rendering and the main loop work as for
+// every other effect.
+
+pub mod blocks;
+pub mod snake;
+
+pub use self::blocks::Blocks;
+pub use self::snake::Snake;
+
+/// the score is shown at least this long before a press starts a new game
+pub const GAME_OVER_MS: u32 = 1000;
+
+/// Show `score` (up to 999) in the middle of the panel, for game over screens
+pub fn draw_score(data: &mut FrameBuffer, score: u32, color: RGB8) {
+    let mut digits = [0u8; 3];
+    let mut n = 0;
+    let mut score = score.min(999);
+    loop {
+        digits[n] = (score % 10) as u8;
+        n += 1;
+        score /= 10;
+        if score == 0 {
+            break;
+        }
+    }
+    // 3 columns per digit plus one column space, centered
+    let left = -(4 * n as i32 - 1) / 2;
+    for i in 0..n {
+        let pos = Vec2::new(left + 4 * i as i32, -2);
+        draw_digit(data, digits[n - 1 - i], pos, color);
+    }
+}
@@ -0,0 +1,530 @@
+use smart_leds::RGB8;
+
+use super::GAME_OVER_MS;
+use crate::{
+    color,
+    effects::Effect,
+    hex::{Cube, CUBE_DIRECTIONS},
+    input::Gesture,
+    led_index_oddr,
+    math::Vec2,
+    panel_cells,
+    rng::Rng,
+    FrameBuffer, NUM_LEDS, PANEL_RADIUS,
+};
+
+// Falling blocks with the seven tetrahexes (pieces of four hex cells). Pieces fall straight down
+// the odd-r rows, i.e. alternately to the south east and south west, and slide sideways on their
+// own, turning around at obstacles. A short press rotates the piece by 60 degrees clockwise, a
+// long press drops it. Full rows are cleared and the rows above move down. The cells on the
+// panel border are not part of the playfield, they show the color of the next piece.
+
+const fn cube(x: i32, y: i32, z: i32) -> Cube {
+    Cube { x, y, z }
+}
+
+/// Cells relative to the rotation center. Pieces are only rotated, so the worm, wave and pistol,
+/// which differ from their mirror images, only come in one handedness.
+const PIECES: [[Cube; 4]; 7] = [
+    // bar
+    [
+        cube(-1, 1, 0),
+        cube(0, 0, 0),
+        cube(1, -1, 0),
+        cube(2, -2, 0),
+    ],
+    // propeller
+    [
+        cube(0, 0, 0),
+        cube(1, -1, 0),
+        cube(0, 1, -1),
+        cube(-1, 0, 1),
+    ],
+    // bee
+    [
+        cube(0, 0, 0),
+        cube(0, -1, 1),
+        cube(1, -1, 0),
+        cube(1, 0, -1),
+    ],
+    // worm
+    [
+        cube(-1, 1, 0),
+        cube(0, 0, 0),
+        cube(1, -1, 0),
+        cube(2, -1, -1),
+    ],
+    // wave
+    [
+        cube(0, 1, -1),
+        cube(0, 0, 0),
+        cube(1, -1, 0),
+        cube(1, -2, 1),
+    ],
+    // arch, around an empty center
+    [
+        cube(1, 0, -1),
+        cube(0, 1, -1),
+        cube(-1, 1, 0),
+        cube(-1, 0, 1),
+    ],
+    // pistol
+    [
+        cube(-1, 1, 0),
+        cube(0, 0, 0),
+        cube(1, -1, 0),
+        cube(1, 0, -1),
+    ],
+];
+
+const COLORS: [RGB8; 7] = [
+    color::CYAN,
+    color::MAGENTA,
+    color::YELLOW,
+    color::BLUE,
+    RGB8 {
+        r: 255,
+        g: 128,
+        b: 0,
+    },
+    color::GREEN,
+    color::RED,
+];
+
+const EAST: Cube = CUBE_DIRECTIONS[0];
+const WEST: Cube = CUBE_DIRECTIONS[3];
+
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    kind: usize,
+    cells: [Cube; 4],
+    origin: Cube,
+}
+
+impl Piece {
+    fn new(kind: usize, origin: Cube) -> Piece {
+        Piece {
+            kind,
+            cells: PIECES[kind],
+            origin,
+        }
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Cube> + '_ {
+        self.cells.iter().map(move |c| *c + self.origin)
+    }
+
+    fn moved(&self, d: Cube) -> Piece {
+        Piece {
+            origin: self.origin + d,
+            ..*self
+        }
+    }
+
+    fn rotated(&self) -> Piece {
+        let mut piece = *self;
+        piece.cells.iter_mut().for_each(|c| *c = c.rotate_right());
+        piece
+    }
+
+    /// one row down, keeping the column of the odd-r layout
+    fn fallen(&self) -> Piece {
+        if self.origin.z & 1 == 0 {
+            self.moved(CUBE_DIRECTIONS[5])
+        } else {
+            self.moved(CUBE_DIRECTIONS[4])
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Playing,
+    GameOver,
+}
+
+pub struct Blocks {
+    /// 0 for empty cells, piece kind + 1 for filled ones
+    board: [u8; NUM_LEDS],
+    playfield: [bool; NUM_LEDS],
+    spawn: Cube,
+    piece: Piece,
+    next: usize,
+    state: State,
+    score: u32,
+    start_fall_ms: u32,
+    fall_ms: u32,
+    /// fastest fall speed, reached after clearing enough rows
+    pub min_fall_ms: u32,
+    /// time per sideways step
+    pub slide_ms: u32,
+    slide: Cube,
+    fall_elapsed: u32,
+    slide_elapsed: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Blocks {
+    /// `fall_ms`: time per row at the start of a game
+    pub fn new(fall_ms: u32, seed: u32) -> Self {
+        // cells with all neighbors on the panel
+        let mut playfield = [false; NUM_LEDS];
+        let mut top = PANEL_RADIUS;
+        for (led, v) in panel_cells() {
+            let c = Cube::from(v);
+            playfield[led] = CUBE_DIRECTIONS
+                .iter()
+                .all(|d| led_index_oddr((c + *d).into()).is_some());
+            if playfield[led] {
+                top = top.min(v.y);
+            }
+        }
+        let spawn = Vec2::new(0, top + 1).into();
+        let mut blocks = Blocks {
+            board: [0; NUM_LEDS],
+            playfield,
+            spawn,
+            piece: Piece::new(0, spawn),
+            next: 0,
+            state: State::Playing,
+            score: 0,
+            start_fall_ms: fall_ms.max(1),
+            fall_ms: fall_ms.max(1),
+            min_fall_ms: fall_ms.max(1) / 4,
+            slide_ms: fall_ms.max(1) / 2,
+            slide: EAST,
+            fall_elapsed: 0,
+            slide_elapsed: 0,
+            elapsed: 0,
+            rng: Rng::new(seed),
+        };
+        blocks.restart();
+        blocks
+    }
+
+    /// start a new game with an empty board
+    pub fn restart(&mut self) {
+        self.board = [0; NUM_LEDS];
+        self.state = State::Playing;
+        self.score = 0;
+        self.fall_ms = self.start_fall_ms;
+        self.fall_elapsed = 0;
+        self.slide_elapsed = 0;
+        self.next = self.rng.range(PIECES.len() as u32) as usize;
+        self.spawn_piece();
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// rows cleared in the current (or last) game
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// kind of the next piece (index into the piece table)
+    pub fn next(&self) -> usize {
+        self.next
+    }
+
+    fn free(&self, c: Cube) -> bool {
+        match led_index_oddr(c.into()) {
+            Some(led) => self.playfield[led] && self.board[led] == 0,
+            None => false,
+        }
+    }
+
+    fn fits(&self, piece: &Piece) -> bool {
+        piece.cells().all(|c| self.free(c))
+    }
+
+    fn spawn_piece(&mut self) {
+        self.piece = Piece::new(self.next, self.spawn);
+        self.next = self.rng.range(PIECES.len() as u32) as usize;
+        self.slide = if self.rng.bool() { EAST } else { WEST };
+        if !self.fits(&self.piece) {
+            self.state = State::GameOver;
+            self.elapsed = 0;
+        }
+    }
+
+    fn lock(&mut self) {
+        for c in self.piece.cells() {
+            if let Some(led) = led_index_oddr(c.into()) {
+                self.board[led] = self.piece.kind as u8 + 1;
+            }
+        }
+        self.clear_rows();
+        self.spawn_piece();
+    }
+
+    fn row_full(&self, y: i32) -> bool {
+        let mut cells = panel_cells().filter(|(led, v)| v.y == y && self.playfield[*led]);
+        let mut any = false;
+        let full = cells.all(|(led, _)| {
+            any = true;
+            self.board[led] != 0
+        });
+        any && full
+    }
+
+    fn clear_rows(&mut self) {
+        for y in -PANEL_RADIUS..=PANEL_RADIUS {
+            if !self.row_full(y) {
+                continue;
+            }
+            // every row above moves down by one, keeping the odd-r column
+            for row in (-PANEL_RADIUS..=y).rev() {
+                for (led, v) in panel_cells().filter(|(_, v)| v.y == row) {
+                    let above = led_index_oddr(Vec2::new(v.x, row - 1));
+                    self.board[led] = match above {
+                        Some(above) if self.playfield[above] => self.board[above],
+                        _ => 0,
+                    };
+                }
+            }
+            self.score += 1;
+            self.fall_ms = (self.fall_ms * 7 / 8).max(self.min_fall_ms);
+        }
+    }
+
+    /// Handle a button gesture: rotate or drop while playing, start over after a game over
+    pub fn input(&mut self, gesture: Gesture) {
+        if self.state == State::GameOver {
+            if self.elapsed >= GAME_OVER_MS {
+                self.restart();
+            }
+            return;
+        }
+        match gesture {
+            Gesture::Short => {
+                // try to make room next to obstacles
+                let rotated = self.piece.rotated();
+                let candidates = [rotated, rotated.moved(EAST), rotated.moved(WEST)];
+                if let Some(piece) = candidates.iter().find(|p| self.fits(p)) {
+                    self.piece = *piece;
+                }
+            }
+            Gesture::Long => {
+                while self.fits(&self.piece.fallen()) {
+                    self.piece = self.piece.fallen();
+                }
+                self.lock();
+                self.fall_elapsed = 0;
+            }
+        }
+    }
+
+    /// move the piece down by one row, or lock it if it can't
+    pub fn fall(&mut self) {
+        if self.state != State::Playing {
+            return;
+        }
+        let fallen = self.piece.fallen();
+        if self.fits(&fallen) {
+            self.piece = fallen;
+        } else {
+            self.lock();
+        }
+    }
+
+    fn slide(&mut self) {
+        let moved = self.piece.moved(self.slide);
+        if self.fits(&moved) {
+            self.piece = moved;
+        } else {
+            self.slide *= -1;
+        }
+    }
+}
+
+impl Effect for Blocks {
+    fn update(&mut self, dt: u32) {
+        if self.state == State::GameOver {
+            self.elapsed = self.elapsed.saturating_add(dt);
+            return;
+        }
+        self.slide_elapsed += dt;
+        while self.slide_elapsed >= self.slide_ms.max(1) {
+            self.slide_elapsed -= self.slide_ms.max(1);
+            self.slide();
+        }
+        self.fall_elapsed += dt;
+        while self.fall_elapsed >= self.fall_ms && self.state == State::Playing {
+            self.fall_elapsed -= self.fall_ms;
+            self.fall();
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        if self.state == State::GameOver {
+            super::draw_score(data, self.score, color::CYAN);
+            return;
+        }
+        let preview = color::scale(COLORS[self.next], 48);
+        for (led, cell) in self.board.iter().enumerate() {
+            data[led] = if !self.playfield[led] {
+                preview
+            } else if *cell > 0 {
+                COLORS[*cell as usize - 1]
+            } else {
+                color::BLACK
+            };
+        }
+        for c in self.piece.cells() {
+            if let Some(led) = led_index_oddr(c.into()) {
+                data[led] = COLORS[self.piece.kind];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_blocks {
+    use super::*;
+
+    fn filled(blocks: &Blocks) -> usize {
+        blocks.board.iter().filter(|c| **c != 0).count()
+    }
+
+    /// the cells as sorted (x, y) pairs, moved so that the first one is at the origin
+    fn shape(cells: &[Cube; 4]) -> [(i32, i32); 4] {
+        let mut shape = [(0, 0); 4];
+        for (s, c) in shape.iter_mut().zip(cells.iter()) {
+            *s = (c.x, c.y);
+        }
+        shape.sort_unstable();
+        let (x, y) = shape[0];
+        shape.iter_mut().for_each(|s| *s = (s.0 - x, s.1 - y));
+        shape
+    }
+
+    fn offsets(piece: &Piece) -> [Vec2; 4] {
+        let mut v = [Vec2::new(0, 0); 4];
+        for (v, c) in v.iter_mut().zip(piece.cells()) {
+            *v = c.into();
+        }
+        v
+    }
+
+    #[test]
+    fn test_pieces() {
+        for cells in PIECES.iter() {
+            // four distinct cells
+            for (i, a) in cells.iter().enumerate() {
+                let v = Vec2::from(*a);
+                assert!(cells[i + 1..].iter().all(|b| Vec2::from(*b) != v));
+            }
+            // flood fill from the first cell reaches all of them
+            let mut reached = [true, false, false, false];
+            for _ in 0..3 {
+                let before = reached;
+                for (i, a) in cells.iter().enumerate() {
+                    reached[i] |= cells
+                        .iter()
+                        .zip(before.iter())
+                        .any(|(b, r)| *r && (*b - *a).length() == 1);
+                }
+            }
+            assert!(reached.iter().all(|r| *r));
+        }
+        // no piece is a rotation of another one
+        for (i, a) in PIECES.iter().enumerate() {
+            for b in PIECES[i + 1..].iter() {
+                let mut b = *b;
+                for _ in 0..6 {
+                    assert_ne!(shape(a), shape(&b));
+                    b.iter_mut().for_each(|c| *c = c.rotate_right());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut blocks = Blocks::new(500, 1);
+        blocks.input(Gesture::Long);
+        assert_eq!(filled(&blocks), 4);
+        // the piece came to rest on the bottom of the playfield
+        let resting = blocks.board.iter().enumerate().any(|(led, cell)| {
+            *cell != 0 && {
+                let (_, v) = panel_cells().find(|(l, _)| *l == led).unwrap();
+                let below = Piece::new(0, Cube::from(v)).fallen().origin;
+                !blocks.free(below)
+            }
+        });
+        assert!(resting);
+        // falling without input ends the same way
+        let mut fallen = Blocks::new(500, 1);
+        fallen.slide_ms = u32::MAX;
+        while filled(&fallen) == 0 {
+            fallen.update(500);
+        }
+        assert_eq!(filled(&fallen), 4);
+        assert_eq!(fallen.state(), State::Playing);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut blocks = Blocks::new(500, 1);
+        let rotated = blocks.piece.rotated();
+        blocks.input(Gesture::Short);
+        assert_eq!(offsets(&blocks.piece), offsets(&rotated));
+        // six rotations bring the piece back
+        let mut piece = rotated;
+        for _ in 0..5 {
+            piece = piece.rotated();
+        }
+        assert_eq!(
+            offsets(&piece),
+            offsets(&Piece::new(rotated.kind, rotated.origin))
+        );
+    }
+
+    #[test]
+    fn test_clear_row() {
+        let mut blocks = Blocks::new(500, 1);
+        let bottom = panel_cells()
+            .filter(|(led, _)| blocks.playfield[*led])
+            .map(|(_, v)| v.y)
+            .max()
+            .unwrap();
+        for (led, v) in panel_cells() {
+            if v.y == bottom && blocks.playfield[led] {
+                blocks.board[led] = 1;
+            }
+        }
+        let x = panel_cells()
+            .find(|(led, v)| v.y == bottom && blocks.playfield[*led])
+            .map(|(_, v)| v.x)
+            .unwrap();
+        let above = led_index_oddr(Vec2::new(x, bottom - 1)).unwrap();
+        blocks.board[above] = 2;
+        blocks.clear_rows();
+        assert_eq!(blocks.score(), 1);
+        assert!(blocks.fall_ms < 500);
+        assert_eq!(filled(&blocks), 1);
+        assert_eq!(
+            blocks.board[led_index_oddr(Vec2::new(x, bottom)).unwrap()],
+            2
+        );
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut blocks = Blocks::new(500, 1);
+        while blocks.state() == State::Playing {
+            blocks.input(Gesture::Long);
+        }
+        let mut data = [color::BLACK; NUM_LEDS];
+        blocks.render(&mut data);
+        blocks.input(Gesture::Short);
+        assert_eq!(blocks.state(), State::GameOver);
+        blocks.update(GAME_OVER_MS);
+        blocks.input(Gesture::Short);
+        assert_eq!((blocks.state(), filled(&blocks)), (State::Playing, 0));
+    }
+}
@@ -0,0 +1,287 @@
+use smart_leds::RGB8;
+
+use super::GAME_OVER_MS;
+use crate::{
+    color,
+    effects::Effect,
+    hex::{Cube, CUBE_DIRECTIONS},
+    input::Gesture,
+    led_index_oddr,
+    math::Vec2,
+    random_panel_cell,
+    rng::Rng,
+    FrameBuffer,
+};
+
+// Snake on the hex panel. The snake moves along CUBE_DIRECTIONS, a short press turns it clockwise,
+// a long press anticlockwise (both by 60 degrees). Eating food makes it longer and faster, running
+// into the panel border or into itself ends the game and shows the score.
+
+const MAX_LENGTH: usize = 64;
+const START_LENGTH: usize = 3;
+/// turns buffered for the next steps
+const MAX_PENDING: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Playing,
+    GameOver,
+}
+
+pub struct Snake {
+    /// ring buffer of the cells the head has been on, body[head] is the head
+    body: [Vec2; MAX_LENGTH],
+    head: usize,
+    len: usize,
+    /// index into CUBE_DIRECTIONS
+    dir: usize,
+    pending: [usize; MAX_PENDING],
+    pending_len: usize,
+    food: Vec2,
+    state: State,
+    score: u32,
+    start_step_ms: u32,
+    step_ms: u32,
+    /// fastest speed, reached after eating enough
+    pub min_step_ms: u32,
+    elapsed: u32,
+    rng: Rng,
+}
+
+impl Snake {
+    /// `step_ms`: time per move at the start of a game
+    pub fn new(step_ms: u32, seed: u32) -> Self {
+        let mut snake = Snake {
+            body: [Vec2::new(0, 0); MAX_LENGTH],
+            head: 0,
+            len: 0,
+            dir: 0,
+            pending: [0; MAX_PENDING],
+            pending_len: 0,
+            food: Vec2::new(0, 0),
+            state: State::Playing,
+            score: 0,
+            start_step_ms: step_ms.max(1),
+            step_ms: step_ms.max(1),
+            min_step_ms: step_ms.max(1) / 3,
+            elapsed: 0,
+            rng: Rng::new(seed),
+        };
+        snake.restart();
+        snake
+    }
+
+    /// start a new game: a short snake in the center heading east
+    pub fn restart(&mut self) {
+        for (i, cell) in self.body[..START_LENGTH].iter_mut().enumerate() {
+            *cell = Vec2::new(i as i32 + 1 - START_LENGTH as i32, 0);
+        }
+        self.head = START_LENGTH - 1;
+        self.len = START_LENGTH;
+        self.dir = 0;
+        self.pending_len = 0;
+        self.state = State::Playing;
+        self.score = 0;
+        self.step_ms = self.start_step_ms;
+        self.elapsed = 0;
+        self.place_food();
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// food eaten in the current (or last) game
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// number of cells of the snake
+    pub fn length(&self) -> usize {
+        self.len
+    }
+
+    pub fn head(&self) -> Vec2 {
+        self.body[self.head]
+    }
+
+    /// cells of the snake from the head to the tail
+    pub fn cells(&self) -> impl Iterator<Item = Vec2> + '_ {
+        (0..self.len).map(move |i| self.body[(self.head + MAX_LENGTH - i) % MAX_LENGTH])
+    }
+
+    fn occupied(&self, v: Vec2) -> bool {
+        self.cells().any(|c| c == v)
+    }
+
+    fn place_food(&mut self) {
+        // the panel is much larger than the snake, so this finds a free cell quickly
+        loop {
+            let v = random_panel_cell(&mut self.rng);
+            if !self.occupied(v) {
+                self.food = v;
+                return;
+            }
+        }
+    }
+
+    /// Handle a button gesture: turn while playing, start over after a game over
+    pub fn input(&mut self, gesture: Gesture) {
+        match self.state {
+            State::Playing => {
+                if self.pending_len < MAX_PENDING {
+                    self.pending[self.pending_len] = match gesture {
+                        Gesture::Short => 5,
+                        Gesture::Long => 1,
+                    };
+                    self.pending_len += 1;
+                }
+            }
+            State::GameOver => {
+                if self.elapsed >= GAME_OVER_MS {
+                    self.restart();
+                }
+            }
+        }
+    }
+
+    /// move by one cell
+    pub fn step(&mut self) {
+        if self.state != State::Playing {
+            return;
+        }
+        if self.pending_len > 0 {
+            self.dir = (self.dir + self.pending[0]) % 6;
+            self.pending.rotate_left(1);
+            self.pending_len -= 1;
+        }
+        let next: Vec2 = (Cube::from(self.head()) + CUBE_DIRECTIONS[self.dir]).into();
+        let eating = next == self.food;
+        // the tail moves on unless the snake grows
+        let body = if eating { self.len } else { self.len - 1 };
+        if led_index_oddr(next).is_none() || self.cells().take(body).any(|c| c == next) {
+            self.state = State::GameOver;
+            self.elapsed = 0;
+            return;
+        }
+        self.head = (self.head + 1) % MAX_LENGTH;
+        self.body[self.head] = next;
+        if eating {
+            self.len = (self.len + 1).min(MAX_LENGTH);
+            self.score += 1;
+            self.step_ms = (self.step_ms * 15 / 16).max(self.min_step_ms);
+            self.place_food();
+        }
+    }
+}
+
+const HEAD: RGB8 = RGB8 {
+    r: 160,
+    g: 255,
+    b: 0,
+};
+const TAIL: RGB8 = RGB8 { r: 0, g: 64, b: 0 };
+
+impl Effect for Snake {
+    fn update(&mut self, dt: u32) {
+        self.elapsed = self.elapsed.saturating_add(dt);
+        if self.state == State::Playing {
+            while self.elapsed >= self.step_ms && self.state == State::Playing {
+                self.elapsed -= self.step_ms;
+                self.step();
+            }
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        data.fill(color::BLACK);
+        if self.state == State::GameOver {
+            super::draw_score(data, self.score, color::YELLOW);
+            return;
+        }
+        if let Some(led) = led_index_oddr(self.food) {
+            data[led] = color::RED;
+        }
+        for (i, v) in self.cells().enumerate() {
+            if let Some(led) = led_index_oddr(v) {
+                data[led] = color::blend(HEAD, TAIL, (i * 255 / self.len) as u8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_snake {
+    use super::*;
+    use crate::panel_cells;
+
+    fn snake() -> Snake {
+        let mut snake = Snake::new(100, 1);
+        // keep the food out of the way
+        snake.food = Vec2::new(0, -8);
+        snake
+    }
+
+    #[test]
+    fn test_wall() {
+        let mut snake = snake();
+        let mut steps = 0;
+        while snake.state() == State::Playing {
+            snake.update(100);
+            steps += 1;
+        }
+        let right = panel_cells()
+            .filter(|(_, v)| v.y == 0)
+            .map(|(_, v)| v.x)
+            .max()
+            .unwrap();
+        assert_eq!(steps, right + 1);
+        assert_eq!(snake.head(), Vec2::new(right, 0));
+        // no restart before the score was visible for a while
+        snake.input(Gesture::Short);
+        assert_eq!(snake.state(), State::GameOver);
+        snake.update(GAME_OVER_MS);
+        snake.input(Gesture::Short);
+        assert_eq!(
+            (snake.state(), snake.head()),
+            (State::Playing, Vec2::new(0, 0))
+        );
+    }
+
+    #[test]
+    fn test_turn_and_eat() {
+        let mut snake = snake();
+        // clockwise from east is south east
+        snake.input(Gesture::Short);
+        snake.step();
+        assert_eq!(snake.head(), Vec2::new(0, 1));
+        snake.input(Gesture::Long);
+        snake.food = Vec2::new(1, 1);
+        snake.step();
+        assert_eq!(
+            (snake.head(), snake.length(), snake.score()),
+            (Vec2::new(1, 1), 4, 1)
+        );
+        assert!(snake.step_ms < 100);
+        assert!(!snake.occupied(snake.food));
+    }
+
+    #[test]
+    fn test_bite() {
+        let mut snake = snake();
+        for _ in 0..4 {
+            snake.step();
+        }
+        // the ring buffer still holds the trail, pretend the snake has grown
+        snake.len = 7;
+        // turning all the time runs around a hexagon, back to the cell behind the starting point
+        for _ in 0..4 {
+            snake.input(Gesture::Long);
+            snake.step();
+            assert_eq!(snake.state(), State::Playing);
+        }
+        snake.input(Gesture::Long);
+        snake.step();
+        assert_eq!(snake.state(), State::GameOver);
+    }
+}
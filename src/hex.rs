@@ -4,7 +4,11 @@ use num_traits::{self, float::FloatCore, Num};
 
 // mostly based on https://www.redblobgames.com/grids/hexagons/
 
-#[derive(Default, Debug, Clone, Copy)]
+/// distance between the centers of neighboring cells in pixel coordinates
+pub const PIXEL_UNIT: i32 = 256;
+
+/// Cell of the hex grid in cube coordinates, the coordinates always sum up to 0
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Cube {
     pub x: i32,
     pub y: i32,
@@ -12,12 +16,165 @@ pub struct Cube {
 }
 
 impl Cube {
+    /// Debug builds panic if x + y + z != 0, see `checked`
     pub fn new(x: i32, y: i32, z: i32) -> Cube {
+        debug_assert!(x + y + z == 0, "cube coordinates must sum up to 0");
         Cube { x, y, z }
     }
+    /// None unless x + y + z == 0
+    pub fn checked(x: i32, y: i32, z: i32) -> Option<Cube> {
+        let c = Cube { x, y, z };
+        if c.is_valid() {
+            Some(c)
+        } else {
+            None
+        }
+    }
     pub fn zero() -> Cube {
         Cube::default()
     }
+    /// true if the coordinates sum up to 0
+    pub fn is_valid(&self) -> bool {
+        self.x + self.y + self.z == 0
+    }
+    /// distance from the origin, i.e. index of the ring around the origin the cube is on
+    pub fn length(&self) -> i32 {
+        self.x.abs().max(self.y.abs()).max(self.z.abs())
+    }
+    /// number of steps between the cells
+    pub fn distance(&self, other: &Cube) -> i32 {
+        cube_distance(self, other)
+    }
+    /// The neighbor in direction `dir` (index into CUBE_DIRECTIONS, wraps around)
+    pub fn neighbor(&self, dir: usize) -> Cube {
+        *self + CUBE_DIRECTIONS[dir % 6]
+    }
+    /// the six neighbors, in the order of CUBE_DIRECTIONS
+    pub fn neighbors(&self) -> [Cube; 6] {
+        let mut n = [*self; 6];
+        for (n, d) in n.iter_mut().zip(CUBE_DIRECTIONS.iter()) {
+            *n += *d;
+        }
+        n
+    }
+    /// The diagonal neighbor in direction `dir` (index into CUBE_DIAGONALS, wraps around)
+    pub fn diagonal(&self, dir: usize) -> Cube {
+        *self + CUBE_DIAGONALS[dir % 6]
+    }
+    /// the six diagonal neighbors, in the order of CUBE_DIAGONALS
+    pub fn diagonals(&self) -> [Cube; 6] {
+        let mut n = [*self; 6];
+        for (n, d) in n.iter_mut().zip(CUBE_DIAGONALS.iter()) {
+            *n += *d;
+        }
+        n
+    }
+    /// Position on the ring around the origin (0..6 * length), counted counter clockwise from
+    /// the corner at CUBE_DIRECTIONS[0] * length. 0 for the origin.
+    pub fn ring_index(&self) -> i32 {
+        let r = self.length();
+        for (k, corner) in CUBE_DIRECTIONS.iter().enumerate() {
+            // side k runs from corner k towards corner k + 1
+            let d = CUBE_DIRECTIONS[(k + 2) % 6];
+            let j = (*self - *corner * r).length();
+            let c = *corner * r + d * j;
+            if j < r && c == *self {
+                return k as i32 * r + j;
+            }
+        }
+        0
+    }
+    /// The cell at position `index` on the ring with `radius` around the origin (inverse of
+    /// `ring_index`, the index wraps around)
+    pub fn ring_cell(radius: i32, index: i32) -> Cube {
+        if radius <= 0 {
+            return Cube::zero();
+        }
+        let index = index.rem_euclid(6 * radius);
+        let k = (index / radius) as usize;
+        CUBE_DIRECTIONS[k] * radius + CUBE_DIRECTIONS[(k + 2) % 6] * (index % radius)
+    }
+    /// Center of the cell in cartesian pixel coordinates (x right, y down), `PIXEL_UNIT` is the
+    /// distance between neighboring cells.
+    pub fn to_pixel(&self) -> (i32, i32) {
+        // pointy top: x = q + r / 2, y = r * sqrt(3) / 2
+        let x = self.x * PIXEL_UNIT + self.z * PIXEL_UNIT / 2;
+        let y = self.z * 222 * PIXEL_UNIT / 256;
+        (x, y)
+    }
+    /// rotated by 60 degrees clockwise around the origin
+    pub fn rotate_right(&self) -> Cube {
+        Cube::new(-self.z, -self.x, -self.y)
+    }
+    /// rotated by 60 degrees counter clockwise around the origin
+    pub fn rotate_left(&self) -> Cube {
+        Cube::new(-self.y, -self.z, -self.x)
+    }
+    /// rotated by 60 degrees clockwise around `center`
+    pub fn rotate_right_around(&self, center: Cube) -> Cube {
+        (*self - center).rotate_right() + center
+    }
+    /// rotated by 60 degrees counter clockwise around `center`
+    pub fn rotate_left_around(&self, center: Cube) -> Cube {
+        (*self - center).rotate_left() + center
+    }
+    /// Mirrored across `axis` through the origin: the `axis` coordinate stays, the other two
+    /// trade places
+    pub fn reflect(&self, axis: Axis) -> Cube {
+        match axis {
+            Axis::X => Cube::new(self.x, self.z, self.y),
+            Axis::Y => Cube::new(self.z, self.y, self.x),
+            Axis::Z => Cube::new(self.y, self.x, self.z),
+        }
+    }
+    /// Mirrored across the line through `center` parallel to `axis`
+    pub fn reflect_around(&self, axis: Axis, center: Cube) -> Cube {
+        (*self - center).reflect(axis) + center
+    }
+    /// The cell containing the pixel position (x, y), see `to_pixel`
+    pub fn from_pixel(x: i32, y: i32) -> Cube {
+        // fractional axial coordinates: q = x - y / sqrt(3), r = y * 2 / sqrt(3)
+        let fq = x - y * 148 / 256;
+        let fr = y * 296 / 256;
+        let fs = -fq - fr;
+        round_scaled(fq, fs, fr, PIXEL_UNIT)
+    }
+    /// Angle around the origin, a full turn is 256. Derived from the ring index, so cells are
+    /// evenly spaced on every ring.
+    pub fn angle(&self) -> u8 {
+        let r = self.length();
+        if r == 0 {
+            0
+        } else {
+            (self.ring_index() * 256 / (6 * r)) as u8
+        }
+    }
+}
+
+/// The cell nearest to (x, y, z) / `d` (x + y + z == 0, d != 0)
+fn round_scaled(x: i32, y: i32, z: i32, d: i32) -> Cube {
+    let (x, y, z, d) = if d < 0 {
+        (-x, -y, -z, -d)
+    } else {
+        (x, y, z, d)
+    };
+    let round = |v: i32| (v + d / 2).div_euclid(d);
+    let (mut rx, mut ry, mut rz) = (round(x), round(y), round(z));
+    let dx = (rx * d - x).abs();
+    let dy = (ry * d - y).abs();
+    let dz = (rz * d - z).abs();
+    if dx > dy && dx > dz {
+        rx = -ry - rz
+    } else if dy > dz {
+        ry = -rx - rz
+    } else {
+        rz = -rx - ry
+    }
+    Cube {
+        x: rx,
+        y: ry,
+        z: rz,
+    }
 }
 
 impl From<&Cube> for Cube {
@@ -48,6 +205,21 @@ impl ops::AddAssign for Cube {
     }
 }
 
+impl ops::SubAssign for Cube {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+
+impl ops::Neg for Cube {
+    type Output = Cube;
+    fn neg(self) -> Self::Output {
+        Cube::new(-self.x, -self.y, -self.z)
+    }
+}
+
 impl ops::Mul<i32> for Cube {
     type Output = Cube;
 
@@ -64,12 +236,42 @@ impl ops::MulAssign<i32> for Cube {
     }
 }
 
+/// Scaled down, rounded to the nearest cell (plain division of the coordinates could break the
+/// sum). Panics for a divisor of 0.
+impl ops::Div<i32> for Cube {
+    type Output = Cube;
+
+    fn div(self, rhs: i32) -> Self::Output {
+        assert!(rhs != 0, "division of a cube by 0");
+        round_scaled(self.x, self.y, self.z, rhs)
+    }
+}
+
 impl From<(i32, i32, i32)> for Cube {
     fn from(v: (i32, i32, i32)) -> Self {
         Cube::new(v.0, v.1, v.2)
     }
 }
 
+/// One of the three axes of the cube coordinate system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// coordinate of `c` along this axis
+    pub fn coord(&self, c: &Cube) -> i32 {
+        match self {
+            Axis::X => c.x,
+            Axis::Y => c.y,
+            Axis::Z => c.z,
+        }
+    }
+}
+
 pub struct Hex {
     pub q: i32,
     pub r: i32,
@@ -117,16 +319,28 @@ pub const CUBE_DIRECTIONS: [Cube; 6] = [
     Cube { x: 0, y: -1, z: 1 },
 ];
 
+/// The cells two steps away between two neighbors, diagonal k lies between CUBE_DIRECTIONS k
+/// and k + 1
+pub const CUBE_DIAGONALS: [Cube; 6] = [
+    Cube { x: 2, y: -1, z: -1 },
+    Cube { x: 1, y: 1, z: -2 },
+    Cube { x: -1, y: 2, z: -1 },
+    Cube { x: -2, y: 1, z: 1 },
+    Cube { x: -1, y: -1, z: 2 },
+    Cube { x: 1, y: -2, z: 1 },
+];
+
 fn lerp<T: Num + Copy>(a: T, b: T, t: T) -> T {
     a + (b - a) * t
 }
 
+/// the cell nearest to the point at `t` (0..=1) on the way from `a` to `b`
 fn cube_lerp(a: &Cube, b: &Cube, t: f32) -> Cube {
-    Cube {
-        x: lerp(a.x as f32, b.x as f32, t) as i32,
-        y: lerp(a.y as f32, b.y as f32, t) as i32,
-        z: lerp(a.z as f32, b.z as f32, t) as i32,
-    }
+    cube_round(
+        lerp(a.x as f32, b.x as f32, t),
+        lerp(a.y as f32, b.y as f32, t),
+        lerp(a.z as f32, b.z as f32, t),
+    )
 }
 
 fn cube_round(x: f32, y: f32, z: f32) -> Cube {
@@ -153,21 +367,25 @@ fn cube_round(x: f32, y: f32, z: f32) -> Cube {
     };
 }
 
-fn cube_distance(a: &Cube, b: &Cube) -> i32 {
-    (a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs() / 2
+/// number of steps between the cells
+pub fn cube_distance(a: &Cube, b: &Cube) -> i32 {
+    ((a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs()) / 2
 }
 
+/// Cells on the line from `a` to `b`, both included, as (number of cells, cells). Only the
+/// first 20 cells of longer lines are returned.
 pub fn cube_linedraw(a: &Cube, b: &Cube) -> (i32, [Cube; 20]) {
     let n = cube_distance(a, b);
     let mut res = [Cube::default(); 20];
-    for i in 0..n.max(20) {
-        let mut c = cube_lerp(a, b, 1f32 / n as f32 * i as f32);
-        c.y = -c.x - c.z;
-        res[i as usize] = c;
+    let count = (n + 1).min(res.len() as i32);
+    for i in 0..count {
+        let t = if n == 0 { 0f32 } else { i as f32 / n as f32 };
+        res[i as usize] = cube_lerp(a, b, t);
     }
-    (n.max(20), res)
+    (count, res)
 }
 
+/// Iterator over the cells on the line from `a` to `b`, `b` itself is not included
 pub struct CubeLinedraw {
     a: Cube,
     b: Cube,
@@ -189,12 +407,9 @@ impl Iterator for CubeLinedraw {
         if self.i >= self.n {
             None
         } else {
-            let t = 1f32 / self.n as f32 * self.i as f32;
-            let x = lerp(self.a.x as f32, self.b.x as f32, t);
-            let y = lerp(self.a.y as f32, self.b.y as f32, t);
-            let z = lerp(self.a.z as f32, self.b.z as f32, t);
+            let t = self.i as f32 / self.n as f32;
             self.i += 1;
-            Some(cube_round(x, y, z))
+            Some(cube_lerp(&self.a, &self.b, t))
         }
     }
 }
@@ -202,3 +417,182 @@ impl Iterator for CubeLinedraw {
 pub mod prelude {
     pub use super::{Cube, Hex};
 }
+
+#[cfg(test)]
+mod test_cube {
+    use super::*;
+    #[test]
+    fn test_ring_index() {
+        for r in 1..5 {
+            let mut seen = [false; 24];
+            for x in -r..=r {
+                for y in -r..=r {
+                    let c = Cube::new(x, y, -x - y);
+                    if c.length() != r {
+                        continue;
+                    }
+                    let i = c.ring_index() as usize;
+                    assert!(i < 6 * r as usize);
+                    assert!(!seen[i]);
+                    seen[i] = true;
+                }
+            }
+            assert_eq!(seen.iter().filter(|s| **s).count(), 6 * r as usize);
+        }
+        assert_eq!(Cube::new(2, -2, 0).ring_index(), 0);
+        assert_eq!(Cube::new(2, -1, -1).ring_index(), 1);
+        assert_eq!(Cube::new(2, 0, -2).angle(), (256 / 6) as u8);
+        for r in 0..5 {
+            for i in 0..6 * r.max(1) {
+                let c = Cube::ring_cell(r, i);
+                assert_eq!(c.length(), r);
+                assert_eq!(c.ring_index(), if r == 0 { 0 } else { i });
+            }
+        }
+    }
+    #[test]
+    fn test_rotate() {
+        let c = Cube::new(3, -1, -2);
+        let (mut l, mut r) = (c, c);
+        for _ in 0..6 {
+            l = l.rotate_left();
+            r = r.rotate_right();
+            assert_eq!(l.length(), 3);
+        }
+        assert_eq!((l.x, l.y, l.z), (c.x, c.y, c.z));
+        assert_eq!((r.x, r.y, r.z), (c.x, c.y, c.z));
+        // CUBE_DIRECTIONS run counter clockwise
+        let d = CUBE_DIRECTIONS[0].rotate_left();
+        assert_eq!((d.x, d.y, d.z), (1, 0, -1));
+        let d = CUBE_DIRECTIONS[0].rotate_right();
+        assert_eq!((d.x, d.y, d.z), (0, -1, 1));
+    }
+    #[test]
+    fn test_pixel_roundtrip() {
+        for x in -10..=10 {
+            for z in -10..=10 {
+                let c = Cube::new(x, -x - z, z);
+                let (px, py) = c.to_pixel();
+                // center and points close to it map back to the same cell
+                for (dx, dy) in [(0, 0), (100, 0), (-100, 0), (50, 80), (-50, -80)].iter() {
+                    let r = Cube::from_pixel(px + dx, py + dy);
+                    assert_eq!((r.x, r.y, r.z), (c.x, c.y, c.z));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_checked() {
+        assert_eq!(Cube::checked(1, -3, 2), Some(Cube::new(1, -3, 2)));
+        assert_eq!(Cube::checked(1, 1, 1), None);
+        assert!(!Cube { x: 1, y: 0, z: 0 }.is_valid());
+        assert!(CUBE_DIRECTIONS.iter().all(|d| d.is_valid()));
+        assert!(CUBE_DIAGONALS.iter().all(|d| d.is_valid()));
+        let c: Cube = (2, -1, -1).into();
+        assert_eq!(c, CUBE_DIAGONALS[0]);
+        assert_ne!(c, -c);
+    }
+    #[test]
+    fn test_distance() {
+        let a = Cube::new(3, -1, -2);
+        let b = Cube::new(-2, 4, -2);
+        // only one coordinate changes in a step along CUBE_DIRECTIONS[2] or [3]
+        assert_eq!(cube_distance(&a, &b), 5);
+        assert_eq!(a.distance(&b), (a - b).length());
+        assert_eq!(b.distance(&a), 5);
+        assert_eq!(a.distance(&a), 0);
+        for (i, d) in CUBE_DIRECTIONS.iter().enumerate() {
+            assert_eq!(a.neighbor(i), a + *d);
+            assert_eq!(a.neighbors()[i].distance(&a), 1);
+            assert_eq!(a.diagonals()[i].distance(&a), 2);
+            // a diagonal is one step further than the neighbors it lies between
+            assert_eq!(a.diagonal(i), a.neighbor(i) + CUBE_DIRECTIONS[(i + 1) % 6]);
+        }
+        assert_eq!(a.neighbor(6), a.neighbor(0));
+    }
+    #[test]
+    fn test_ops() {
+        let a = Cube::new(3, -1, -2);
+        assert_eq!(-a, Cube::new(-3, 1, 2));
+        assert_eq!(a + -a, Cube::zero());
+        let mut b = a;
+        b -= CUBE_DIRECTIONS[0];
+        assert_eq!(b, Cube::new(2, 0, -2));
+        assert_eq!(a * 4 / 4, a);
+        assert_eq!(a * -2 / -2, a);
+        // rounded to the nearest cell instead of truncated
+        assert_eq!(Cube::new(2, -1, -1) / 2, Cube::new(1, 0, -1));
+        assert_eq!(Cube::new(6, -3, -3) / 4, Cube::new(2, -1, -1));
+        for x in -6..=6 {
+            for y in -6..=6 {
+                let c = Cube::new(x, y, -x - y);
+                for d in [2, 3, -3, 7].iter() {
+                    let q = c / *d;
+                    assert!(q.is_valid());
+                    assert!((q * *d).distance(&c) <= d.abs());
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_rotate_around() {
+        let center = Cube::new(2, -3, 1);
+        let c = Cube::new(-1, 0, 1);
+        let (mut l, mut r) = (c, c);
+        for _ in 0..6 {
+            l = l.rotate_left_around(center);
+            r = r.rotate_right_around(center);
+            assert_eq!(l.distance(&center), c.distance(&center));
+            assert_eq!(r.distance(&center), c.distance(&center));
+        }
+        assert_eq!((l, r), (c, c));
+        assert_eq!(center.rotate_left_around(center), center);
+        assert_eq!(c.rotate_left_around(center).rotate_right_around(center), c);
+        // three steps either way end up opposite of the center
+        let mut l = c;
+        for _ in 0..3 {
+            l = l.rotate_left_around(center);
+        }
+        assert_eq!(l, center * 2 - c);
+    }
+    #[test]
+    fn test_reflect() {
+        let c = Cube::new(3, -1, -2);
+        for axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let m = c.reflect(*axis);
+            assert_eq!(axis.coord(&m), axis.coord(&c));
+            assert_eq!(m.length(), c.length());
+            assert_eq!(m.reflect(*axis), c);
+        }
+        assert_eq!(c.reflect(Axis::X), Cube::new(3, -2, -1));
+        // east and west are mirror images across z
+        assert_eq!(CUBE_DIRECTIONS[0].reflect(Axis::Z), CUBE_DIRECTIONS[3]);
+        let center = Cube::new(1, 1, -2);
+        assert_eq!(center.reflect_around(Axis::Y, center), center);
+        assert_eq!(
+            c.reflect_around(Axis::Y, center)
+                .reflect_around(Axis::Y, center),
+            c
+        );
+    }
+    #[test]
+    fn test_linedraw() {
+        let a = Cube::new(-3, 0, 3);
+        let b = Cube::new(4, -5, 1);
+        let (n, cells) = cube_linedraw(&a, &b);
+        assert_eq!(n, a.distance(&b) + 1);
+        assert_eq!((cells[0], cells[n as usize - 1]), (a, b));
+        for w in cells[..n as usize].windows(2) {
+            assert_eq!(w[0].distance(&w[1]), 1);
+        }
+        assert_eq!(cube_linedraw(&a, &a).0, 1);
+        // the iterator gives the same cells, but stops before the end point
+        assert_eq!(CubeLinedraw::new(a, b).count(), n as usize - 1);
+        assert!(CubeLinedraw::new(a, b)
+            .zip(cells.iter())
+            .all(|(c, d)| c == *d));
+        // long lines are cut off
+        let (n, cells) = cube_linedraw(&Cube::zero(), &Cube::new(30, -30, 0));
+        assert_eq!((n, cells[19]), (20, Cube::new(19, -19, 0)));
+    }
+}
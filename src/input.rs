@@ -0,0 +1,137 @@
+// Hardware independent input handling: the firmware samples the raw button state (see
+// `io::button_pressed`) once per frame and feeds it in together with the elapsed time.
+
+/// Button debouncing without blocking: a new state is only accepted after it was stable for
+/// `debounce_ms`.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    debounce_ms: u32,
+    pressed: bool,
+    stable_ms: u32,
+}
+
+impl Debouncer {
+    pub fn new(debounce_ms: u32) -> Self {
+        Debouncer {
+            debounce_ms,
+            pressed: false,
+            stable_ms: 0,
+        }
+    }
+
+    /// debounced state
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Feed the raw button state `dt` ms after the last call. Returns the new state if it changed.
+    pub fn update(&mut self, raw: bool, dt: u32) -> Option<bool> {
+        if raw == self.pressed {
+            self.stable_ms = 0;
+            return None;
+        }
+        self.stable_ms += dt;
+        if self.stable_ms >= self.debounce_ms {
+            self.pressed = raw;
+            self.stable_ms = 0;
+            Some(raw)
+        } else {
+            None
+        }
+    }
+}
+
+/// What the user did with a button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    /// pressed and released again before the long press time
+    Short,
+    /// held down for the long press time, reported while the button is still down
+    Long,
+}
+
+/// Tells short from long presses of a (debounced) button
+#[derive(Debug, Clone)]
+pub struct Gestures {
+    debouncer: Debouncer,
+    long_ms: u32,
+    held_ms: u32,
+    long_sent: bool,
+}
+
+impl Gestures {
+    pub fn new(debounce_ms: u32, long_ms: u32) -> Self {
+        Gestures {
+            debouncer: Debouncer::new(debounce_ms),
+            long_ms,
+            held_ms: 0,
+            long_sent: false,
+        }
+    }
+
+    /// Feed the raw button state `dt` ms after the last call. Returns a gesture once it is
+    /// complete.
+    pub fn update(&mut self, raw: bool, dt: u32) -> Option<Gesture> {
+        match self.debouncer.update(raw, dt) {
+            Some(true) => {
+                self.held_ms = 0;
+                self.long_sent = false;
+                None
+            }
+            Some(false) if !self.long_sent => Some(Gesture::Short),
+            Some(false) => None,
+            None if self.debouncer.is_pressed() && !self.long_sent => {
+                self.held_ms += dt;
+                if self.held_ms >= self.long_ms {
+                    self.long_sent = true;
+                    Some(Gesture::Long)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_input {
+    use super::*;
+
+    #[test]
+    fn test_debounce() {
+        let mut button = Debouncer::new(30);
+        // bouncing contact
+        for raw in [true, false, true, false].iter() {
+            assert_eq!(button.update(*raw, 10), None);
+        }
+        assert_eq!(button.update(true, 10), None);
+        assert_eq!(button.update(true, 10), None);
+        assert_eq!(button.update(true, 10), Some(true));
+        assert!(button.is_pressed());
+        assert_eq!(button.update(true, 10), None);
+        assert_eq!(button.update(false, 40), Some(false));
+    }
+
+    #[test]
+    fn test_gestures() {
+        let mut button = Gestures::new(20, 300);
+        let mut press = |ms: u32| -> [Option<Gesture>; 2] {
+            let mut gestures = [None; 2];
+            let mut n = 0;
+            let mut t = 0;
+            while t < ms + 100 {
+                if let Some(g) = button.update(t < ms, 10) {
+                    gestures[n] = Some(g);
+                    n += 1;
+                }
+                t += 10;
+            }
+            gestures
+        };
+        assert_eq!(press(100), [Some(Gesture::Short), None]);
+        // reported once, not again on release
+        assert_eq!(press(1000), [Some(Gesture::Long), None]);
+        assert_eq!(press(5), [None, None]);
+    }
+}
@@ -4,10 +4,24 @@
 
 use smart_leds::RGB8;
 
+pub mod audio;
+pub mod automaton;
+pub mod beat;
 pub mod bitzet;
+pub mod clock;
+pub mod easing;
 pub mod effects;
+pub mod fixed;
+pub mod games;
 pub mod hex;
+pub mod input;
 pub mod math;
+pub mod maze;
+pub mod particles;
+pub mod playlist;
+pub mod rng;
+pub mod search;
+pub mod timeline;
 pub use stm32l4xx_hal as hal;
 
 pub mod setup {
@@ -19,6 +33,7 @@ pub mod setup {
         gpio::{gpioa::PA5, gpioc::PC13, Alternate, GpioExt, Input, PullUp},
         prelude::*,
         rcc::{RccExt, APB2},
+        rng::{Rng, RngExt},
         spi::Spi,
     };
     use smart_leds::{SmartLedsWrite, RGB8};
@@ -85,6 +100,21 @@ pub mod setup {
         pub delay: Delay,
         pub spi1: SPI1,
         pub apb2: APB2,
+        /// hardware random number generator, e.g. for seeding `rng::Rng::from_source`
+        pub rng: Rng,
+    }
+
+    impl crate::clock::Clock for hal::rtc::Rtc {
+        fn now(&self) -> crate::clock::Time {
+            let (_, time) = self.get_date_time();
+            crate::clock::Time::new(time.hours as u8, time.minutes as u8, time.seconds as u8)
+        }
+    }
+
+    impl crate::rng::SeedSource for Rng {
+        fn seed(&mut self) -> u32 {
+            self.get_random_data()
+        }
     }
 
     pub fn setup() -> Option<Periphery> {
@@ -98,11 +128,13 @@ pub mod setup {
             let mut pwr = p.PWR.constrain(&mut rcc.apb1r1);
             let clocks = rcc // full speed (64 & 80MHz) use the 16MHZ HSI osc + PLL (but slower / intermediate values need MSI)
                 .cfgr
+                .hsi48(true) // clock for the RNG
                 .sysclk(80.mhz())
                 .pclk1(80.mhz())
                 .pclk2(80.mhz())
                 .freeze(&mut flash.acr, &mut pwr);
 
+            let rng = p.RNG.enable(&mut rcc.ahb2, clocks, &mut rcc.crrcr);
             let gpioa = p.GPIOA.split(&mut rcc.ahb2);
             let gpioc = p.GPIOC.split(&mut rcc.ahb2);
             // Get delay provider
@@ -117,6 +149,7 @@ pub mod setup {
                 delay,
                 spi1: p.SPI1,
                 apb2: rcc.apb2,
+                rng,
             })
         } else {
             None
@@ -139,8 +172,15 @@ pub mod io {
             }
         }
     }
+
+    /// Raw (not debounced) state of the user button, see `input::Debouncer`
+    pub fn button_pressed<B: InputPin<Error = Infallible>>(button: &B) -> bool {
+        button.is_low().unwrap()
+    }
 }
 pub const NUM_LEDS: usize = 291;
+/// one color per led, in led chain order
+pub type FrameBuffer = [RGB8; NUM_LEDS];
 const MATRIX_MAP: [i16; 21 * 19] = [
     291, 291, 291, 291, 291, 291, 291, 291, 0, 1, 2, 3, 4, 5, 6, 7, 291, 291, 291, 291, 291, 291,
     291, 291, 291, 291, 16, 15, 14, 13, 12, 11, 10, 9, 8, 291, 291, 291, 291, 291, 291, 291, 291,
@@ -165,6 +205,8 @@ const MATRIX_MAP: [i16; 21 * 19] = [
 ];
 pub const MATRIX_WIDTH: usize = 19;
 pub const MATRIX_HEIGHT: usize = 21;
+/// number of rings around the panel center (the outer rings are not complete)
+pub const PANEL_RADIUS: i32 = 10;
 
 pub enum Error {
     OutOfBounds,
@@ -176,6 +218,21 @@ pub fn set_matrix_oddr(v: math::Vec2, color: RGB8, data: &mut [RGB8; NUM_LEDS])
     }
 }
 
+/// led index at odd-r coordinate `v` (relative to the panel center), if it is on the panel
+pub fn led_index_oddr(v: math::Vec2) -> Option<usize> {
+    let x = v.x + PANEL_RADIUS;
+    let y = v.y + PANEL_RADIUS;
+    if x < 0 || y < 0 || x as usize >= MATRIX_WIDTH || y as usize >= MATRIX_HEIGHT {
+        return None;
+    }
+    let led = MATRIX_MAP[x as usize + y as usize * MATRIX_WIDTH] as usize;
+    if led < NUM_LEDS {
+        Some(led)
+    } else {
+        None
+    }
+}
+
 pub fn set_matrix(
     x: usize,
     y: usize,
@@ -192,6 +249,28 @@ pub fn set_matrix(
     Ok(*led)
 }
 
+/// Iterate over all leds of the panel as (led index, odd-r coordinate relative to the panel
+/// center), i.e. the inverse of `set_matrix_oddr`. Iteration is in matrix order, not led order.
+pub fn panel_cells() -> impl Iterator<Item = (usize, math::Vec2)> {
+    MATRIX_MAP
+        .iter()
+        .enumerate()
+        .filter(|(_, led)| (**led as usize) < NUM_LEDS)
+        .map(|(addr, led)| {
+            let x = (addr % MATRIX_WIDTH) as i32 - PANEL_RADIUS;
+            let y = (addr / MATRIX_WIDTH) as i32 - PANEL_RADIUS;
+            (*led as usize, math::Vec2::new(x, y))
+        })
+}
+
+/// A random cell of the panel, in odd-r coordinates relative to the panel center
+pub fn random_panel_cell(rng: &mut rng::Rng) -> math::Vec2 {
+    let (_, v) = panel_cells()
+        .nth(rng.range(NUM_LEDS as u32) as usize)
+        .unwrap();
+    v
+}
+
 pub fn get_matrix(x: usize, y: usize, data: &mut [RGB8; NUM_LEDS]) -> Result<(i16, RGB8), Error> {
     if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
         return Err(Error::OutOfBounds);
@@ -247,6 +326,36 @@ pub mod color {
         (wheel_pos * 3, 255 - wheel_pos * 3, 0).into()
     }
 
+    /// Linear interpolation between `a` (t = 0) and `b` (t = 255)
+    pub fn blend(a: RGB8, b: RGB8, t: u8) -> RGB8 {
+        let t = t as u16;
+        let mix = |a: u8, b: u8| ((a as u16 * (255 - t) + b as u16 * t) / 255) as u8;
+        RGB8 {
+            r: mix(a.r, b.r),
+            g: mix(a.g, b.g),
+            b: mix(a.b, b.b),
+        }
+    }
+
+    /// Component wise saturating addition
+    pub fn add(a: RGB8, b: RGB8) -> RGB8 {
+        RGB8 {
+            r: a.r.saturating_add(b.r),
+            g: a.g.saturating_add(b.g),
+            b: a.b.saturating_add(b.b),
+        }
+    }
+
+    /// Scale color by `s / 256` (same as smart_leds::brightness, but for a single value)
+    pub fn scale(c: RGB8, s: u8) -> RGB8 {
+        let s = s as u16 + 1;
+        RGB8 {
+            r: (c.r as u16 * s / 256) as u8,
+            g: (c.g as u16 * s / 256) as u8,
+            b: (c.b as u16 * s / 256) as u8,
+        }
+    }
+
     pub const BLACK: RGB8 = RGB8 { r: 0, g: 0, b: 0 };
     pub const RED: RGB8 = RGB8 { r: 255, g: 0, b: 0 };
     pub const GREEN: RGB8 = RGB8 { r: 0, g: 255, b: 0 };
@@ -266,6 +375,116 @@ pub mod color {
         g: 255,
         b: 0,
     };
+    pub const WHITE: RGB8 = RGB8 {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    /// Maps an index 0..=255 to a color
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Palette {
+        /// the color wheel (see `wheel`)
+        Wheel,
+        /// the same color for every index
+        Solid(RGB8),
+        /// linear gradient between (index, color) stops, sorted by index
+        Gradient(&'static [(u8, RGB8)]),
+    }
+
+    impl Palette {
+        pub fn color(&self, index: u8) -> RGB8 {
+            match self {
+                Palette::Wheel => wheel(index),
+                Palette::Solid(c) => *c,
+                Palette::Gradient(stops) => {
+                    let mut prev = match stops.first() {
+                        Some(first) => *first,
+                        None => return BLACK,
+                    };
+                    for stop in stops.iter() {
+                        if index <= stop.0 {
+                            let range = (stop.0 - prev.0) as u16;
+                            if range == 0 {
+                                return stop.1;
+                            }
+                            let t = (index - prev.0) as u16 * 255 / range;
+                            return blend(prev.1, stop.1, t as u8);
+                        }
+                        prev = *stop;
+                    }
+                    prev.1
+                }
+            }
+        }
+    }
+
+    pub const HEAT: Palette = Palette::Gradient(&[
+        (0, BLACK),
+        (96, RGB8 { r: 255, g: 0, b: 0 }),
+        (
+            192,
+            RGB8 {
+                r: 255,
+                g: 160,
+                b: 0,
+            },
+        ),
+        (
+            255,
+            RGB8 {
+                r: 255,
+                g: 255,
+                b: 160,
+            },
+        ),
+    ]);
+    pub const OCEAN: Palette = Palette::Gradient(&[
+        (0, RGB8 { r: 0, g: 0, b: 32 }),
+        (
+            96,
+            RGB8 {
+                r: 0,
+                g: 32,
+                b: 255,
+            },
+        ),
+        (
+            192,
+            RGB8 {
+                r: 0,
+                g: 192,
+                b: 192,
+            },
+        ),
+        (
+            255,
+            RGB8 {
+                r: 160,
+                g: 255,
+                b: 255,
+            },
+        ),
+    ]);
+    pub const FOREST: Palette = Palette::Gradient(&[
+        (0, RGB8 { r: 0, g: 32, b: 0 }),
+        (
+            128,
+            RGB8 {
+                r: 32,
+                g: 192,
+                b: 0,
+            },
+        ),
+        (
+            255,
+            RGB8 {
+                r: 192,
+                g: 255,
+                b: 32,
+            },
+        ),
+    ]);
 }
 
 pub mod canvas {
@@ -299,8 +518,9 @@ pub mod canvas {
 }
 pub mod prelude {
     pub use super::{
-        canvas::Canvas, color::Rainbow, effects, get_matrix, hal, io::button_wait_debounced,
-        set_matrix, set_matrix_oddr, setup::setup, setup::setup_simple, setup::Periphery,
-        MATRIX_HEIGHT, MATRIX_WIDTH, NUM_LEDS,
+        canvas::Canvas, color::Rainbow, effects, effects::Effect, get_matrix, hal,
+        io::button_pressed, io::button_wait_debounced, led_index_oddr, panel_cells, set_matrix,
+        set_matrix_oddr, setup::setup, setup::setup_simple, setup::Periphery, FrameBuffer,
+        MATRIX_HEIGHT, MATRIX_WIDTH, NUM_LEDS, PANEL_RADIUS,
     };
 }
@@ -0,0 +1,326 @@
+use crate::{
+    hex::{Cube, CUBE_DIRECTIONS},
+    led_index_oddr,
+    math::Vec2,
+    panel_cells,
+    rng::Rng,
+    NUM_LEDS,
+};
+
+// Mazes on the panel cells. The maze nodes are the cells with even cube x and z, i.e. every second
+// cell in each direction. The cell between two neighboring nodes is the wall between them, or the
+// passage once it is carved. Every panel cell is either a node or between two nodes, so the
+// whole panel shows the maze. A finished maze is a spanning tree of the nodes: there is exactly
+// one way between any two of them, which `search::Search` finds with `Maze::cost`.
+// Generation runs one step at a time for animation and only uses fixed size arrays.
+
+/// upper bound for the number of nodes on the panel
+const MAX_NODES: usize = 96;
+
+/// How the maze is carved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    /// Random depth first walk, backing up at dead ends: long winding corridors
+    Backtracker,
+    /// Grows the maze at a random node of its frontier: many short dead ends
+    Prim,
+    /// Loop-erased random walks from random nodes until they hit the maze: uniformly random
+    /// mazes, slow at the start
+    Wilson,
+}
+
+/// State of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Wall,
+    /// part of the maze
+    Passage,
+    /// being worked on: on the backtracker stack, on the Prim frontier or on the Wilson walk
+    Active,
+}
+
+/// true for the cells that are maze nodes
+pub fn is_node(c: Cube) -> bool {
+    c.x & 1 == 0 && c.z & 1 == 0
+}
+
+/// Directions (indices into CUBE_DIRECTIONS) to the neighboring nodes of node `c` that are on
+/// the panel along with the cell in between, only the first `count` entries are valid
+fn links(c: Cube) -> ([usize; 6], usize) {
+    let mut links = [0; 6];
+    let mut count = 0;
+    for (i, d) in CUBE_DIRECTIONS.iter().enumerate() {
+        let on_panel = |c: Cube| led_index_oddr(c.into()).is_some();
+        if on_panel(c + *d) && on_panel(c + *d * 2) {
+            links[count] = i;
+            count += 1;
+        }
+    }
+    (links, count)
+}
+
+pub struct Maze {
+    generator: Generator,
+    marks: [Mark; NUM_LEDS],
+    /// backtracker: the path from the start node
+    stack: [Cube; MAX_NODES],
+    stack_len: usize,
+    /// Wilson: last direction the walk left each node in, following them from the walk start
+    /// gives the walk without loops
+    exits: [u8; NUM_LEDS],
+    walk: Option<(Cube, Cube)>,
+    done: bool,
+    rng: Rng,
+}
+
+impl Maze {
+    /// A maze with all walls in place, the first node of the maze is the center
+    pub fn new(generator: Generator, seed: u32) -> Maze {
+        let mut maze = Maze {
+            generator,
+            marks: [Mark::Wall; NUM_LEDS],
+            stack: [Cube::zero(); MAX_NODES],
+            stack_len: 0,
+            exits: [0; NUM_LEDS],
+            walk: None,
+            done: false,
+            rng: Rng::new(seed),
+        };
+        match generator {
+            Generator::Backtracker => {
+                maze.set(Cube::zero(), Mark::Active);
+                maze.stack_len = 1;
+            }
+            Generator::Prim => maze.add(Cube::zero()),
+            Generator::Wilson => maze.set(Cube::zero(), Mark::Passage),
+        }
+        maze
+    }
+
+    pub fn generator(&self) -> Generator {
+        self.generator
+    }
+
+    /// true once every node is part of the maze
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// state of `c`, cells off the panel are walls
+    pub fn mark(&self, c: Cube) -> Mark {
+        match led_index_oddr(c.into()) {
+            Some(led) => self.marks[led],
+            None => Mark::Wall,
+        }
+    }
+
+    fn set(&mut self, c: Cube, mark: Mark) {
+        if let Some(led) = led_index_oddr(c.into()) {
+            self.marks[led] = mark;
+        }
+    }
+
+    /// Cost function for `search::Search`: passages cost 1, the rest is blocked
+    pub fn cost(&self, c: Cube) -> Option<u8> {
+        if self.mark(c) == Mark::Passage {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    /// the neighbor nodes of `c` with the given mark, as direction indices
+    fn neighbors(&self, c: Cube, mark: Mark) -> ([usize; 6], usize) {
+        let (mut links, count) = links(c);
+        let mut n = 0;
+        for i in 0..count {
+            if self.mark(c + CUBE_DIRECTIONS[links[i]] * 2) == mark {
+                links[n] = links[i];
+                n += 1;
+            }
+        }
+        (links, n)
+    }
+
+    /// random node with the given mark, None if there is none
+    fn random_node(&mut self, mark: Mark) -> Option<Cube> {
+        let marks = &self.marks;
+        let candidates = |(led, v): &(usize, Vec2)| marks[*led] == mark && is_node((*v).into());
+        let count = panel_cells().filter(candidates).count();
+        if count == 0 {
+            return None;
+        }
+        let i = self.rng.range(count as u32) as usize;
+        panel_cells()
+            .filter(candidates)
+            .nth(i)
+            .map(|(_, v)| v.into())
+    }
+
+    /// Prim: make `c` part of the maze and its neighbors part of the frontier
+    fn add(&mut self, c: Cube) {
+        self.set(c, Mark::Passage);
+        let (links, count) = self.neighbors(c, Mark::Wall);
+        for d in links[..count].iter() {
+            self.set(c + CUBE_DIRECTIONS[*d] * 2, Mark::Active);
+        }
+    }
+
+    /// Carve one passage (or take one step of a walk), returns false once the maze is done
+    pub fn step(&mut self) -> bool {
+        if self.done {
+            return false;
+        }
+        match self.generator {
+            Generator::Backtracker => self.step_backtracker(),
+            Generator::Prim => self.step_prim(),
+            Generator::Wilson => self.step_wilson(),
+        }
+        !self.done
+    }
+
+    /// run until the maze is done
+    pub fn generate(&mut self) {
+        while self.step() {}
+    }
+
+    fn step_backtracker(&mut self) {
+        if self.stack_len == 0 {
+            self.done = true;
+            return;
+        }
+        let c = self.stack[self.stack_len - 1];
+        let (links, count) = self.neighbors(c, Mark::Wall);
+        if count == 0 || self.stack_len == MAX_NODES {
+            self.set(c, Mark::Passage);
+            self.stack_len -= 1;
+            return;
+        }
+        let d = CUBE_DIRECTIONS[links[self.rng.range(count as u32) as usize]];
+        self.set(c + d, Mark::Passage);
+        self.set(c + d * 2, Mark::Active);
+        self.stack[self.stack_len] = c + d * 2;
+        self.stack_len += 1;
+    }
+
+    fn step_prim(&mut self) {
+        let c = match self.random_node(Mark::Active) {
+            Some(c) => c,
+            None => {
+                self.done = true;
+                return;
+            }
+        };
+        let (links, count) = self.neighbors(c, Mark::Passage);
+        if count > 0 {
+            let d = CUBE_DIRECTIONS[links[self.rng.range(count as u32) as usize]];
+            self.set(c + d, Mark::Passage);
+        }
+        self.add(c);
+    }
+
+    fn step_wilson(&mut self) {
+        let (start, c) = match self.walk {
+            Some(walk) => walk,
+            None => {
+                match self.random_node(Mark::Wall) {
+                    Some(start) => {
+                        self.set(start, Mark::Active);
+                        self.walk = Some((start, start));
+                    }
+                    None => self.done = true,
+                }
+                return;
+            }
+        };
+        let (links, count) = links(c);
+        let exit = links[self.rng.range(count as u32) as usize];
+        if let Some(led) = led_index_oddr(c.into()) {
+            self.exits[led] = exit as u8;
+        }
+        let next = c + CUBE_DIRECTIONS[exit] * 2;
+        if self.mark(next) != Mark::Passage {
+            self.set(next, Mark::Active);
+            self.walk = Some((start, next));
+            return;
+        }
+        // reached the maze: carve the walk without its loops, forget the rest
+        let mut c = start;
+        while self.mark(c) != Mark::Passage {
+            let d = match led_index_oddr(c.into()) {
+                Some(led) => CUBE_DIRECTIONS[self.exits[led] as usize],
+                None => break,
+            };
+            self.set(c, Mark::Passage);
+            self.set(c + d, Mark::Passage);
+            c += d * 2;
+        }
+        for mark in self.marks.iter_mut() {
+            if *mark == Mark::Active {
+                *mark = Mark::Wall;
+            }
+        }
+        self.walk = None;
+    }
+}
+
+#[cfg(test)]
+mod test_maze {
+    use super::*;
+    use crate::search::{find_path, Algorithm, Status};
+
+    #[test]
+    fn test_nodes() {
+        let nodes = panel_cells().filter(|(_, v)| is_node((*v).into())).count();
+        assert!(nodes <= MAX_NODES);
+        // every cell is a node or between two nodes
+        for (_, v) in panel_cells() {
+            let c = Cube::from(v);
+            assert!(
+                is_node(c)
+                    || CUBE_DIRECTIONS
+                        .iter()
+                        .any(|d| is_node(c + *d) && is_node(c - *d))
+            );
+        }
+    }
+
+    #[test]
+    fn test_generators() {
+        for generator in [Generator::Backtracker, Generator::Prim, Generator::Wilson].iter() {
+            let mut maze = Maze::new(*generator, 1);
+            let mut steps = 0;
+            while maze.step() {
+                steps += 1;
+                assert!(steps < 100_000);
+            }
+            assert!(maze.is_done());
+            let cells = |mark: Mark, node: bool| {
+                panel_cells()
+                    .filter(|(_, v)| maze.mark((*v).into()) == mark && is_node((*v).into()) == node)
+                    .count()
+            };
+            let nodes = panel_cells().filter(|(_, v)| is_node((*v).into())).count();
+            // a spanning tree: all nodes connected by one passage less than there are nodes
+            assert_eq!(cells(Mark::Passage, true), nodes);
+            assert_eq!(cells(Mark::Passage, false), nodes - 1);
+            assert_eq!(cells(Mark::Active, false) + cells(Mark::Active, true), 0);
+            for (_, v) in panel_cells().filter(|(_, v)| is_node((*v).into())) {
+                let search = find_path(Algorithm::Bfs, Cube::zero(), v.into(), |c| maze.cost(c));
+                assert_eq!(search.status(), Status::Found);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut a = Maze::new(Generator::Prim, 3);
+        let mut b = Maze::new(Generator::Prim, 3);
+        let mut c = Maze::new(Generator::Prim, 4);
+        a.generate();
+        b.generate();
+        c.generate();
+        assert!(a.marks == b.marks);
+        assert!(a.marks != c.marks);
+    }
+}
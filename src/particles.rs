@@ -0,0 +1,302 @@
+use smart_leds::RGB8;
+
+use crate::{
+    color::{self, Palette},
+    fixed::{cos16, sin16},
+    hex::{Cube, PIXEL_UNIT},
+    led_index_oddr,
+    rng::Rng,
+    FrameBuffer, PANEL_RADIUS,
+};
+
+// Fixed capacity particle system in continuous 2d pixel coordinates (see Cube::to_pixel, x
+// right, y down, origin in the panel center). Particles are rendered additively into the hex
+// cell they are in, fading out with their remaining life.
+
+/// inner radius of the panel hexagon (the big hexagon formed by the cells has flat top and bottom)
+const PANEL_INRADIUS: i32 = (2 * PANEL_RADIUS + 1) * 222 * PIXEL_UNIT / 512;
+/// longer updates are integrated in steps of this length (ms)
+const MAX_STEP_MS: u32 = 50;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Particle {
+    /// position in pixel coordinates
+    pub x: i32,
+    pub y: i32,
+    /// velocity in pixel units per second
+    pub vx: i32,
+    pub vy: i32,
+    /// remaining life (ms), the particle is dead at 0
+    pub life: u16,
+    /// initial life, used to fade out the color
+    pub max_life: u16,
+    pub color: RGB8,
+}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.life > 0
+    }
+    /// color faded according to the remaining life
+    pub fn current_color(&self) -> RGB8 {
+        let level = self.life as u32 * 255 / self.max_life.max(1) as u32;
+        color::scale(self.color, level as u8)
+    }
+}
+
+/// What happens to particles leaving the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// keep going, they may come back (e.g. under gravity)
+    Open,
+    /// die immediately
+    Kill,
+    /// reflect at the panel border, keeping `restitution` / 256 of the speed
+    Bounce(u8),
+}
+
+// distance function of the panel hexagon: > PANEL_INRADIUS means outside. Returns the outward
+// normal of the closest side (length 256) as well.
+fn panel_distance(x: i32, y: i32) -> (i32, (i32, i32)) {
+    let sx = if x < 0 { -1 } else { 1 };
+    let sy = if y < 0 { -1 } else { 1 };
+    // slanted sides have normals at +-30 degrees: (cos 30, sin 30)
+    let slanted = (x.abs() * 222 + y.abs() * 128) / 256;
+    if y.abs() >= slanted {
+        (y.abs(), (0, sy * 256))
+    } else {
+        (slanted, (sx * 222, sy * 128))
+    }
+}
+
+pub struct ParticleSystem<const N: usize> {
+    particles: [Particle; N],
+    /// acceleration in pixel units per second^2 (e.g. (0, 2000) for gravity)
+    pub gravity: (i32, i32),
+    /// fraction (of 256) of the velocity lost per second
+    pub drag: u8,
+    pub boundary: Boundary,
+}
+
+impl<const N: usize> ParticleSystem<N> {
+    pub fn new(gravity: (i32, i32), drag: u8, boundary: Boundary) -> Self {
+        ParticleSystem {
+            particles: [Particle::default(); N],
+            gravity,
+            drag,
+            boundary,
+        }
+    }
+
+    /// Add a particle, replacing the one closest to its end if all slots are in use
+    pub fn spawn(&mut self, p: Particle) {
+        if let Some(slot) =
+            self.particles
+                .iter_mut()
+                .min_by_key(|p| if p.is_alive() { p.life as u32 + 1 } else { 0 })
+        {
+            *slot = p;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter().filter(|p| p.is_alive())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Particle> {
+        self.particles.iter_mut().filter(|p| p.is_alive())
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.iter_mut().for_each(|p| p.life = 0);
+    }
+
+    pub fn update(&mut self, dt: u32) {
+        // every particle is dead after u16::MAX ms, there is no point in integrating any further
+        let mut dt = dt.min(u16::MAX as u32 + 1);
+        while dt > MAX_STEP_MS {
+            self.step(MAX_STEP_MS as i32);
+            dt -= MAX_STEP_MS;
+        }
+        self.step(dt as i32);
+    }
+
+    /// advance by `dt` ms, at most MAX_STEP_MS: the velocity change by drag stays small and the
+    /// products stay within i32
+    fn step(&mut self, dt: i32) {
+        let (gx, gy) = self.gravity;
+        let drag = self.drag as i32;
+        let boundary = self.boundary;
+        for p in self.iter_mut() {
+            p.life = p.life.saturating_sub(dt as u16);
+            p.vx += gx * dt / 1000;
+            p.vy += gy * dt / 1000;
+            p.vx -= p.vx * drag * dt / (256 * 1000);
+            p.vy -= p.vy * drag * dt / (256 * 1000);
+            p.x += p.vx * dt / 1000;
+            p.y += p.vy * dt / 1000;
+
+            match boundary {
+                Boundary::Open => (),
+                Boundary::Kill => {
+                    if panel_distance(p.x, p.y).0 > PANEL_INRADIUS {
+                        p.life = 0;
+                    }
+                }
+                Boundary::Bounce(restitution) => {
+                    // near a corner pushing back from one side can end up outside the other one
+                    for _ in 0..2 {
+                        let (d, (nx, ny)) = panel_distance(p.x, p.y);
+                        if d <= PANEL_INRADIUS {
+                            break;
+                        }
+                        // move back inside (+1 against rounding) and reflect the velocity
+                        // component along the normal
+                        let overshoot = d - PANEL_INRADIUS + 1;
+                        p.x -= overshoot * nx / 256;
+                        p.y -= overshoot * ny / 256;
+                        let dot = (p.vx * nx + p.vy * ny) / 256;
+                        if dot > 0 {
+                            p.vx -= 2 * dot * nx / 256;
+                            p.vy -= 2 * dot * ny / 256;
+                            p.vx = p.vx * restitution as i32 / 256;
+                            p.vy = p.vy * restitution as i32 / 256;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add all particles onto `data` (without clearing it)
+    pub fn render(&self, data: &mut FrameBuffer) {
+        for p in self.iter() {
+            if let Some(led) = led_index_oddr(Cube::from_pixel(p.x, p.y).into()) {
+                data[led] = color::add(data[led], p.current_color());
+            }
+        }
+    }
+}
+
+/// Spawns particles at a steady rate from a point, in a cone of directions
+#[derive(Debug, Clone, Copy)]
+pub struct Emitter {
+    pub x: i32,
+    pub y: i32,
+    /// particles per second
+    pub rate: u32,
+    /// main direction (full turn = 65536, 0 = right, 16384 = down)
+    pub angle: u16,
+    /// total opening angle of the cone
+    pub spread: u16,
+    /// speed range in pixel units per second
+    pub speed: (i32, i32),
+    /// life range in ms
+    pub life: (u16, u16),
+    pub palette: Palette,
+    elapsed: u32,
+}
+
+impl Emitter {
+    pub fn new(x: i32, y: i32, rate: u32, palette: Palette) -> Self {
+        Emitter {
+            x,
+            y,
+            rate,
+            angle: 0,
+            spread: 0xffff,
+            speed: (PIXEL_UNIT, 4 * PIXEL_UNIT),
+            life: (500, 1500),
+            palette,
+            elapsed: 0,
+        }
+    }
+
+    /// create a single particle according to the emitter settings
+    pub fn particle(&self, rng: &mut Rng) -> Particle {
+        let angle = self
+            .angle
+            .wrapping_sub(self.spread / 2)
+            .wrapping_add(rng.range(self.spread as u32 + 1) as u16);
+        let speed = rng.between(self.speed.0, self.speed.1);
+        let life = rng.between(self.life.0 as i32, self.life.1 as i32) as u16;
+        Particle {
+            x: self.x,
+            y: self.y,
+            vx: cos16(angle) as i32 * speed / 32767,
+            vy: sin16(angle) as i32 * speed / 32767,
+            life,
+            max_life: life,
+            color: self.palette.color(rng.next_u32() as u8),
+        }
+    }
+
+    /// spawn the particles due after `dt` ms into `system`
+    pub fn emit<const N: usize>(&mut self, dt: u32, system: &mut ParticleSystem<N>, rng: &mut Rng) {
+        if self.rate == 0 {
+            return;
+        }
+        self.elapsed += dt;
+        let interval = (1000 / self.rate).max(1);
+        while self.elapsed >= interval {
+            self.elapsed -= interval;
+            system.spawn(self.particle(rng));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_particles {
+    use super::*;
+
+    #[test]
+    fn test_bounce_stays_inside() {
+        let mut system = ParticleSystem::<8>::new((0, 0), 0, Boundary::Bounce(255));
+        system.spawn(Particle {
+            vx: 20 * PIXEL_UNIT,
+            vy: 7 * PIXEL_UNIT,
+            life: 10_000,
+            max_life: 10_000,
+            color: color::WHITE,
+            ..Particle::default()
+        });
+        for _ in 0..500 {
+            system.update(10);
+            let p = system.iter().next().unwrap();
+            assert!(panel_distance(p.x, p.y).0 <= PANEL_INRADIUS);
+        }
+    }
+
+    #[test]
+    fn test_kill_outside() {
+        let mut system = ParticleSystem::<8>::new((0, 4000), 0, Boundary::Kill);
+        system.spawn(Particle {
+            life: 10_000,
+            max_life: 10_000,
+            ..Particle::default()
+        });
+        for _ in 0..300 {
+            system.update(10);
+        }
+        assert_eq!(system.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_long_step() {
+        let mut system = ParticleSystem::<8>::new((0, 0), 32, Boundary::Open);
+        system.spawn(Particle {
+            vy: 16 * PIXEL_UNIT,
+            life: 60_000,
+            max_life: 60_000,
+            ..Particle::default()
+        });
+        system.update(9_000);
+        // drag slowed the particle down without turning it around
+        let p = system.iter().next().unwrap();
+        assert!(p.vy > 0 && p.vy < 16 * PIXEL_UNIT / 2);
+        assert!(p.y > 16 * PIXEL_UNIT);
+        // more than u16::MAX ms
+        system.update(65_536 + 100);
+        assert_eq!(system.iter().count(), 0);
+    }
+}
@@ -0,0 +1,395 @@
+use smart_leds::RGB8;
+
+use crate::{
+    color,
+    effects::Effect,
+    hex::{Axis, Cube},
+    panel_cells,
+    rng::{hash32, Rng},
+    FrameBuffer, NUM_LEDS, PANEL_RADIUS,
+};
+
+// Runs a sequence of effects with per-entry durations and blends between consecutive entries.
+// During a transition both effects are updated and rendered into separate buffers, which are
+// then mixed per led according to the transition of the entry that is coming up.
+
+/// width (in key units 0..255) of the soft edge of the wipe / dissolve transitions
+const EDGE: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// switch immediately
+    Cut,
+    /// fade all leds at the same time
+    Crossfade,
+    /// wipe along one of the hex axes (negative to positive side)
+    Wipe(Axis),
+    /// ring wipe from the center outwards
+    Radial,
+    /// leds switch over in random order
+    Dissolve,
+}
+
+impl Transition {
+    /// Mix `to` into `from` for progress `t` (0: only `from`, 255: only `to`). `seed`
+    /// selects the led order of `Dissolve`.
+    pub fn apply(&self, from: &mut FrameBuffer, to: &FrameBuffer, t: u8, seed: u32) {
+        match self {
+            Transition::Cut => from.copy_from_slice(to),
+            Transition::Crossfade => from
+                .iter_mut()
+                .zip(to.iter())
+                .for_each(|(a, b)| *a = color::blend(*a, *b, t)),
+            Transition::Wipe(axis) => mix_keyed(from, to, t, |_, c| {
+                let d = 2 * PANEL_RADIUS;
+                ((axis.coord(&c) + PANEL_RADIUS).clamp(0, d) * 255 / d) as u8
+            }),
+            Transition::Radial => mix_keyed(from, to, t, |_, c| {
+                (c.length().min(PANEL_RADIUS) * 255 / PANEL_RADIUS) as u8
+            }),
+            Transition::Dissolve => {
+                mix_keyed(from, to, t, |led, _| hash32(led as u32 ^ seed) as u8)
+            }
+        }
+    }
+}
+
+// leds with a lower key switch over first, with a soft edge of EDGE key units
+fn mix_keyed<F: Fn(usize, Cube) -> u8>(from: &mut FrameBuffer, to: &FrameBuffer, t: u8, key: F) {
+    // at t = 255 the edge has passed the highest key
+    let front = t as u32 * (255 + EDGE) / 255;
+    for (led, v) in panel_cells() {
+        let k = key(led, v.into()) as u32;
+        let w = (front.saturating_sub(k) * 255 / EDGE).min(255);
+        from[led] = color::blend(from[led], to[led], w as u8);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// play entries in the given order
+    Sequential,
+    /// play all entries in random order before any entry is repeated
+    Shuffle,
+    /// pick the next entry uniformly at random
+    Random,
+    /// pick the next entry at random, proportional to the entry weights
+    Weighted,
+}
+
+pub struct Entry<'a> {
+    pub effect: &'a mut dyn Effect,
+    /// time (ms) the effect is shown on its own
+    pub duration: u32,
+    /// relative probability for Order::Weighted
+    pub weight: u8,
+    /// transition used when switching to this entry
+    pub transition: Transition,
+    /// duration (ms) of the transition
+    pub transition_ms: u32,
+}
+
+impl<'a> Entry<'a> {
+    pub fn new(effect: &'a mut dyn Effect, duration: u32) -> Self {
+        Entry {
+            effect,
+            duration,
+            weight: 1,
+            transition: Transition::Crossfade,
+            transition_ms: 1000,
+        }
+    }
+    pub fn weight(mut self, weight: u8) -> Self {
+        self.weight = weight;
+        self
+    }
+    pub fn transition(mut self, transition: Transition, transition_ms: u32) -> Self {
+        self.transition = transition;
+        self.transition_ms = transition_ms;
+        self
+    }
+}
+
+pub struct Playlist<'a, const N: usize> {
+    entries: [Entry<'a>; N],
+    order: Order,
+    rng: Rng,
+    current: usize,
+    next: Option<usize>,
+    elapsed: u32,
+    transition_elapsed: u32,
+    transition_seed: u32,
+    shuffled: [usize; N],
+    shuffle_pos: usize,
+}
+
+impl<'a, const N: usize> Playlist<'a, N> {
+    pub fn new(entries: [Entry<'a>; N], order: Order, seed: u32) -> Self {
+        let mut shuffled = [0; N];
+        shuffled.iter_mut().enumerate().for_each(|(i, s)| *s = i);
+
+        Playlist {
+            entries,
+            order,
+            rng: Rng::new(seed),
+            current: 0,
+            next: None,
+            elapsed: 0,
+            transition_elapsed: 0,
+            transition_seed: 0,
+            shuffled,
+            shuffle_pos: N,
+        }
+    }
+
+    /// index of the entry currently playing (the outgoing one during a transition)
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// true while blending between two entries
+    pub fn in_transition(&self) -> bool {
+        self.next.is_some()
+    }
+
+    fn pick_next(&mut self) -> usize {
+        if N < 2 {
+            return 0;
+        }
+        match self.order {
+            Order::Sequential => (self.current + 1) % N,
+            Order::Shuffle => {
+                if self.shuffle_pos >= N {
+                    self.rng.shuffle(&mut self.shuffled);
+                    // no direct repetition across shuffle rounds
+                    if self.shuffled[0] == self.current {
+                        self.shuffled.swap(0, N - 1);
+                    }
+                    self.shuffle_pos = 0;
+                }
+                self.shuffle_pos += 1;
+                self.shuffled[self.shuffle_pos - 1]
+            }
+            Order::Random => {
+                let i = self.rng.range(N as u32 - 1) as usize;
+                if i >= self.current {
+                    i + 1
+                } else {
+                    i
+                }
+            }
+            Order::Weighted => {
+                let current = self.current;
+                let weight = |i: usize, e: &Entry| if i == current { 0 } else { e.weight as u32 };
+                let total: u32 = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| weight(i, e))
+                    .sum();
+                if total == 0 {
+                    return current;
+                }
+                let mut r = self.rng.range(total);
+                for (i, e) in self.entries.iter().enumerate() {
+                    let w = weight(i, e);
+                    if r < w {
+                        return i;
+                    }
+                    r -= w;
+                }
+                current
+            }
+        }
+    }
+
+    fn switch_to(&mut self, next: usize, elapsed: u32) {
+        self.current = next;
+        self.next = None;
+        self.elapsed = elapsed;
+    }
+}
+
+impl<'a, const N: usize> Effect for Playlist<'a, N> {
+    fn update(&mut self, dt: u32) {
+        if N == 0 {
+            return;
+        }
+        self.entries[self.current].effect.update(dt);
+        match self.next {
+            Some(next) => {
+                self.entries[next].effect.update(dt);
+                self.transition_elapsed += dt;
+                if self.transition_elapsed >= self.entries[next].transition_ms {
+                    self.switch_to(next, self.transition_elapsed);
+                }
+            }
+            None => {
+                self.elapsed += dt;
+                let entry = &self.entries[self.current];
+                if self.elapsed < entry.duration && !entry.effect.is_finished() {
+                    return;
+                }
+                let next = self.pick_next();
+                let entry = &self.entries[next];
+                if next == self.current
+                    || entry.transition == Transition::Cut
+                    || entry.transition_ms == 0
+                {
+                    self.switch_to(next, 0);
+                } else {
+                    self.next = Some(next);
+                    self.transition_elapsed = 0;
+                    self.transition_seed = self.rng.next_u32();
+                }
+            }
+        }
+    }
+
+    fn render(&self, data: &mut FrameBuffer) {
+        if N == 0 {
+            return;
+        }
+        self.entries[self.current].effect.render(data);
+        if let Some(next) = self.next {
+            let entry = &self.entries[next];
+            let mut to = [RGB8::default(); NUM_LEDS];
+            entry.effect.render(&mut to);
+            let t = (self.transition_elapsed.min(entry.transition_ms) * 255 / entry.transition_ms)
+                as u8;
+            entry.transition.apply(data, &to, t, self.transition_seed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_playlist {
+    use super::*;
+
+    struct Solid(RGB8);
+    impl Effect for Solid {
+        fn update(&mut self, _dt: u32) {}
+        fn render(&self, data: &mut FrameBuffer) {
+            data.iter_mut().for_each(|v| *v = self.0);
+        }
+    }
+
+    #[test]
+    fn test_sequential() {
+        let mut a = Solid(color::RED);
+        let mut b = Solid(color::BLUE);
+        let mut playlist = Playlist::new(
+            [
+                Entry::new(&mut a, 100),
+                Entry::new(&mut b, 100).transition(Transition::Crossfade, 50),
+            ],
+            Order::Sequential,
+            1,
+        );
+        let mut data = [RGB8::default(); NUM_LEDS];
+        playlist.update(100);
+        assert!(playlist.in_transition());
+        playlist.update(25);
+        playlist.render(&mut data);
+        assert!(data[0].r > 0 && data[0].b > 0);
+        playlist.update(25);
+        assert!(!playlist.in_transition());
+        assert_eq!(playlist.current(), 1);
+        playlist.render(&mut data);
+        assert_eq!(data[0], color::BLUE);
+    }
+
+    struct Countdown(u32);
+    impl Effect for Countdown {
+        fn update(&mut self, dt: u32) {
+            self.0 = self.0.saturating_sub(dt);
+        }
+        fn render(&self, _data: &mut FrameBuffer) {}
+        fn is_finished(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    #[test]
+    fn test_finished_early() {
+        let mut a = Countdown(30);
+        let mut b = Solid(color::BLUE);
+        let mut playlist = Playlist::new(
+            [
+                Entry::new(&mut a, 1000),
+                Entry::new(&mut b, 1000).transition(Transition::Cut, 0),
+            ],
+            Order::Sequential,
+            1,
+        );
+        playlist.update(20);
+        assert_eq!(playlist.current(), 0);
+        playlist.update(10);
+        assert_eq!(playlist.current(), 1);
+    }
+
+    #[test]
+    fn test_weighted_skips_zero_weight() {
+        let mut a = Solid(color::RED);
+        let mut b = Solid(color::GREEN);
+        let mut c = Solid(color::BLUE);
+        let mut playlist = Playlist::new(
+            [
+                Entry::new(&mut a, 10).transition(Transition::Cut, 0),
+                Entry::new(&mut b, 10)
+                    .transition(Transition::Cut, 0)
+                    .weight(0),
+                Entry::new(&mut c, 10).transition(Transition::Cut, 0),
+            ],
+            Order::Weighted,
+            1234,
+        );
+        for _ in 0..100 {
+            playlist.update(10);
+            assert_ne!(playlist.current(), 1);
+        }
+    }
+
+    #[test]
+    fn test_shuffle_plays_all() {
+        let mut a = Solid(color::RED);
+        let mut b = Solid(color::GREEN);
+        let mut c = Solid(color::BLUE);
+        let mut playlist = Playlist::new(
+            [
+                Entry::new(&mut a, 10).transition(Transition::Cut, 0),
+                Entry::new(&mut b, 10).transition(Transition::Cut, 0),
+                Entry::new(&mut c, 10).transition(Transition::Cut, 0),
+            ],
+            Order::Shuffle,
+            42,
+        );
+        let mut seen = [false; 3];
+        for _ in 0..3 {
+            playlist.update(10);
+            seen[playlist.current()] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+    }
+
+    #[test]
+    fn test_transition_endpoints() {
+        let from = [color::RED; NUM_LEDS];
+        let to = [color::BLUE; NUM_LEDS];
+        for transition in [
+            Transition::Crossfade,
+            Transition::Wipe(Axis::X),
+            Transition::Radial,
+            Transition::Dissolve,
+        ]
+        .iter()
+        {
+            let mut data = from;
+            transition.apply(&mut data, &to, 0, 7);
+            assert!(data.iter().all(|v| *v == color::RED));
+            let mut data = from;
+            transition.apply(&mut data, &to, 255, 7);
+            assert!(data.iter().all(|v| *v == color::BLUE));
+        }
+    }
+}
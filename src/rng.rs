@@ -0,0 +1,155 @@
+// Small seedable pseudo random number generator (xorshift32). Not suitable for anything but
+// visual effects, but cheap and reproducible (same seed -> same sequence, also on the host).
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        // xorshift gets stuck on 0
+        let state = if seed == 0 { 0x9e37_79b9 } else { seed };
+        Rng { state }
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+    /// Generator seeded from `source` (e.g. the hardware RNG on the board, a fixed value in tests)
+    pub fn from_source<S: SeedSource>(source: &mut S) -> Rng {
+        Rng::new(source.seed())
+    }
+    pub fn next_u8(&mut self) -> u8 {
+        // high bits are the better ones for xorshift
+        (self.next_u32() >> 24) as u8
+    }
+    /// random value in 0..n (n must not be 0)
+    pub fn range(&mut self, n: u32) -> u32 {
+        ((self.next_u32() as u64 * n as u64) >> 32) as u32
+    }
+    /// random value in min..=max (min if max < min)
+    pub fn between(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            min
+        } else {
+            let span = (max as i64 - min as i64 + 1) as u64;
+            (min as i64 + ((self.next_u32() as u64 * span) >> 32) as i64) as i32
+        }
+    }
+    pub fn bool(&mut self) -> bool {
+        self.next_u32() & 0x8000_0000 != 0
+    }
+    /// true with a probability of `p` / 256
+    pub fn chance(&mut self, p: u8) -> bool {
+        self.next_u8() < p
+    }
+    /// random element of `items`, None if it is empty
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.range(items.len() as u32) as usize)
+        }
+    }
+    /// Fisher-Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.range(i as u32 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Source of seeds for `Rng`. Implemented for the STM32L4 hardware RNG in `setup`, for sampled
+/// noise (`NoiseSeed`) and for plain values (`FixedSeed`, for reproducible host tests).
+pub trait SeedSource {
+    fn seed(&mut self) -> u32;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FixedSeed(pub u32);
+
+impl SeedSource for FixedSeed {
+    fn seed(&mut self) -> u32 {
+        self.0
+    }
+}
+
+/// Collects the least significant bits of 32 samples of some noisy measurement, e.g. an ADC
+/// reading of a floating pin or of the internal temperature sensor.
+pub struct NoiseSeed<F>(pub F);
+
+impl<F: FnMut() -> u16> SeedSource for NoiseSeed<F> {
+    fn seed(&mut self) -> u32 {
+        let mut seed = 0;
+        for _ in 0..32 {
+            seed = (seed << 1) | ((self.0)() & 1) as u32;
+        }
+        // the bits are usually biased, spread them out
+        hash32(seed)
+    }
+}
+
+/// Stateless integer hash (lowbias32 by Chris Wellons), handy to derive a stable random value
+/// per led from an index and a seed.
+pub fn hash32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+#[cfg(test)]
+mod test_rng {
+    use super::*;
+
+    #[test]
+    fn test_reproducible() {
+        let mut a = Rng::from_source(&mut FixedSeed(42));
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn test_between() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let v = rng.between(-3, 3);
+            assert!((-3..=3).contains(&v));
+            seen[(v + 3) as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+        assert_eq!(rng.between(5, 5), 5);
+    }
+
+    #[test]
+    fn test_choose_shuffle() {
+        let mut rng = Rng::new(7);
+        assert_eq!(rng.choose::<u8>(&[]), None);
+        assert_eq!(rng.choose(&[3]), Some(&3));
+
+        let mut items = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        rng.shuffle(&mut items);
+        assert_ne!(items, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        items.sort_unstable();
+        assert_eq!(items, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_noise_seed() {
+        let mut samples = [812u16, 813, 813, 810, 811].iter().cycle().cloned();
+        let a = NoiseSeed(|| samples.next().unwrap()).seed();
+        assert_ne!(a, 0);
+        assert_ne!(a, NoiseSeed(|| 812).seed());
+    }
+}
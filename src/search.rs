@@ -0,0 +1,323 @@
+use crate::{
+    hex::{Cube, CUBE_DIRECTIONS},
+    led_index_oddr, panel_cells, NUM_LEDS,
+};
+
+// Shortest paths between cells of the panel. The search state lives in arrays indexed by led, so
+// it needs no allocation and can be advanced one cell at a time for animations. The open cell
+// with the lowest priority is found by scanning the panel, which is fast enough for 291 cells
+// and avoids a priority queue. Cells are passable if the cost function returns Some(cost) for
+// them, the cost is paid for entering a cell.
+
+/// How the next cell to expand is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// breadth first: every step costs 1, the costs only tell which cells are passable
+    Bfs,
+    /// lowest cost from the start first
+    Dijkstra,
+    /// lowest cost from the start plus cube distance to the goal first
+    AStar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Searching,
+    Found,
+    /// every reachable cell was visited without reaching the goal
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Unseen,
+    /// on the frontier
+    Open,
+    /// visited
+    Closed,
+}
+
+/// `from` value of the start cell
+const START: u8 = 6;
+
+pub struct Search {
+    algorithm: Algorithm,
+    goal: Cube,
+    status: Status,
+    marks: [Mark; NUM_LEDS],
+    costs: [u16; NUM_LEDS],
+    /// index into CUBE_DIRECTIONS of the step that reached the cell
+    from: [u8; NUM_LEDS],
+    visited: usize,
+}
+
+impl Search {
+    /// Start a search from `start` to `goal`, nothing is expanded yet
+    pub fn new(algorithm: Algorithm, start: Cube, goal: Cube) -> Search {
+        let mut search = Search {
+            algorithm,
+            goal,
+            status: Status::Unreachable,
+            marks: [Mark::Unseen; NUM_LEDS],
+            costs: [0; NUM_LEDS],
+            from: [START; NUM_LEDS],
+            visited: 0,
+        };
+        if let (Some(led), Some(_)) = (led_index_oddr(start.into()), led_index_oddr(goal.into())) {
+            search.marks[led] = Mark::Open;
+            search.status = Status::Searching;
+        }
+        search
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    fn heuristic(&self, c: Cube) -> u32 {
+        match self.algorithm {
+            Algorithm::AStar => (c - self.goal).length() as u32,
+            _ => 0,
+        }
+    }
+
+    /// Expand the open cell with the lowest priority. `cost` returns the cost of entering a cell
+    /// (at least 1 is charged), None for walls.
+    pub fn step<F: Fn(Cube) -> Option<u8>>(&mut self, cost: F) -> Status {
+        if self.status != Status::Searching {
+            return self.status;
+        }
+        // ties go to the cell closer to the goal, which keeps A* going straight
+        let next = panel_cells()
+            .filter(|(led, _)| self.marks[*led] == Mark::Open)
+            .map(|(led, v)| {
+                let h = self.heuristic(v.into());
+                (led, Cube::from(v), self.costs[led] as u32 + h, h)
+            })
+            .min_by_key(|(_, _, f, h)| (*f, *h));
+        let (led, c) = match next {
+            Some((led, c, _, _)) => (led, c),
+            None => {
+                self.status = Status::Unreachable;
+                return self.status;
+            }
+        };
+        self.marks[led] = Mark::Closed;
+        self.visited += 1;
+        if led_index_oddr(c.into()) == led_index_oddr(self.goal.into()) {
+            self.status = Status::Found;
+            return self.status;
+        }
+        for (d, dir) in CUBE_DIRECTIONS.iter().enumerate() {
+            let n = c + *dir;
+            let n_led = match led_index_oddr(n.into()) {
+                Some(n_led) if self.marks[n_led] != Mark::Closed => n_led,
+                _ => continue,
+            };
+            let step = match (cost(n), self.algorithm) {
+                (None, _) => continue,
+                (Some(_), Algorithm::Bfs) => 1,
+                (Some(step), _) => step.max(1) as u16,
+            };
+            let total = self.costs[led].saturating_add(step);
+            if self.marks[n_led] == Mark::Unseen || total < self.costs[n_led] {
+                self.marks[n_led] = Mark::Open;
+                self.costs[n_led] = total;
+                self.from[n_led] = d as u8;
+            }
+        }
+        self.status
+    }
+
+    /// Run to the end, see `step`
+    pub fn run<F: Fn(Cube) -> Option<u8>>(&mut self, cost: F) -> Status {
+        while self.step(&cost) == Status::Searching {}
+        self.status
+    }
+
+    /// number of cells expanded so far
+    pub fn visited_count(&self) -> usize {
+        self.visited
+    }
+
+    fn mark(&self, c: Cube) -> Mark {
+        match led_index_oddr(c.into()) {
+            Some(led) => self.marks[led],
+            None => Mark::Unseen,
+        }
+    }
+
+    pub fn is_visited(&self, c: Cube) -> bool {
+        self.mark(c) == Mark::Closed
+    }
+
+    /// true for cells that were reached but not expanded yet
+    pub fn is_frontier(&self, c: Cube) -> bool {
+        self.mark(c) == Mark::Open
+    }
+
+    /// the expanded cells, in led order
+    pub fn visited(&self) -> impl Iterator<Item = Cube> + '_ {
+        panel_cells()
+            .filter(move |(led, _)| self.marks[*led] == Mark::Closed)
+            .map(|(_, v)| v.into())
+    }
+
+    /// the cells on the frontier, in led order
+    pub fn frontier(&self) -> impl Iterator<Item = Cube> + '_ {
+        panel_cells()
+            .filter(move |(led, _)| self.marks[*led] == Mark::Open)
+            .map(|(_, v)| v.into())
+    }
+
+    /// cost of the best path found so far to `c`, None if it was not reached
+    pub fn cost(&self, c: Cube) -> Option<u32> {
+        match led_index_oddr(c.into()) {
+            Some(led) if self.marks[led] != Mark::Unseen => Some(self.costs[led] as u32),
+            _ => None,
+        }
+    }
+
+    /// The shortest path from the goal back to the start (both included), empty unless the goal
+    /// was found
+    pub fn path(&self) -> Path<'_> {
+        Path {
+            search: self,
+            next: if self.status == Status::Found {
+                Some(self.goal)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Cells of a path, see `Search::path`
+pub struct Path<'a> {
+    search: &'a Search,
+    next: Option<Cube>,
+}
+
+impl<'a> Iterator for Path<'a> {
+    type Item = Cube;
+
+    fn next(&mut self) -> Option<Cube> {
+        let c = self.next?;
+        let d = self.search.from[led_index_oddr(c.into())?];
+        self.next = CUBE_DIRECTIONS.get(d as usize).map(|d| c - *d);
+        Some(c)
+    }
+}
+
+/// Run a complete search, the result holds the path and the visited cells
+pub fn find_path<F: Fn(Cube) -> Option<u8>>(
+    algorithm: Algorithm,
+    start: Cube,
+    goal: Cube,
+    cost: F,
+) -> Search {
+    let mut search = Search::new(algorithm, start, goal);
+    search.run(cost);
+    search
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::*;
+    use crate::math::Vec2;
+
+    fn open(_: Cube) -> Option<u8> {
+        Some(1)
+    }
+
+    #[test]
+    fn test_open() {
+        let start = Cube::new(-4, 4, 0);
+        let goal = Cube::new(3, -1, -2);
+        let distance = (goal - start).length() as usize;
+        let mut visited = [0; 3];
+        for (i, algorithm) in [Algorithm::Bfs, Algorithm::Dijkstra, Algorithm::AStar]
+            .iter()
+            .enumerate()
+        {
+            let search = find_path(*algorithm, start, goal, open);
+            assert_eq!(search.status(), Status::Found);
+            assert_eq!(search.path().count(), distance + 1);
+            assert_eq!(search.cost(goal), Some(distance as u32));
+            let last = search.path().last().unwrap();
+            assert_eq!(Vec2::from(last), Vec2::from(start));
+            visited[i] = search.visited_count();
+        }
+        // the heuristic saves a lot of work
+        assert!(visited[2] < visited[0] / 2);
+        assert_eq!(visited[0], visited[1]);
+    }
+
+    #[test]
+    fn test_wall() {
+        // a wall across the panel with a gap at the right end
+        let wall = |c: Cube| {
+            let v = Vec2::from(c);
+            if v.y == 0 && v.x < 5 {
+                None
+            } else {
+                Some(1)
+            }
+        };
+        let start = Vec2::new(0, -3).into();
+        let goal = Vec2::new(0, 3).into();
+        let search = find_path(Algorithm::AStar, start, goal, wall);
+        assert_eq!(search.status(), Status::Found);
+        assert!(search.path().any(|c| Vec2::from(c) == Vec2::new(5, 0)));
+        assert!(search.path().all(|c| wall(c).is_some()));
+        // closing the gap separates the halves
+        let closed = |c: Cube| if Vec2::from(c).y == 0 { None } else { Some(1) };
+        let search = find_path(Algorithm::Bfs, start, goal, closed);
+        assert_eq!(search.status(), Status::Unreachable);
+        assert_eq!(search.path().count(), 0);
+        assert!(search.visited().all(|c| Vec2::from(c).y < 0));
+        assert!(search.is_visited(start) && !search.is_visited(goal));
+    }
+
+    #[test]
+    fn test_costs() {
+        // expensive cells in the direct line, the cheap way around is longer
+        let swamp = |c: Cube| {
+            if c.z == 0 && c.x.abs() < 3 {
+                Some(20)
+            } else {
+                Some(1)
+            }
+        };
+        let start = Cube::new(0, 3, -3);
+        let goal = Cube::new(0, -3, 3);
+        let bfs = find_path(Algorithm::Bfs, start, goal, swamp);
+        assert_eq!(bfs.path().count(), 7);
+        for algorithm in [Algorithm::Dijkstra, Algorithm::AStar].iter() {
+            let search = find_path(*algorithm, start, goal, swamp);
+            assert!(search.path().count() > 7);
+            assert!(search.path().all(|c| swamp(c) == Some(1)));
+            assert_eq!(search.cost(goal), Some(search.path().count() as u32 - 1));
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut search = Search::new(Algorithm::Bfs, Cube::zero(), Cube::new(2, -2, 0));
+        assert_eq!(search.step(open), Status::Searching);
+        assert_eq!(search.frontier().count(), 6);
+        assert!(search.is_frontier(Cube::new(0, 1, -1)));
+        // the first ring is expanded before anything further away
+        for _ in 0..6 {
+            search.step(open);
+        }
+        assert!(search.visited().all(|c| c.length() <= 1));
+        assert_eq!(search.run(open), Status::Found);
+        let off_panel = Search::new(Algorithm::Bfs, Cube::zero(), Cube::new(20, -20, 0));
+        assert_eq!(off_panel.status(), Status::Unreachable);
+    }
+}
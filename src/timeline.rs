@@ -0,0 +1,206 @@
+use smart_leds::RGB8;
+
+use crate::easing::{progress, Easing, ONE};
+
+// Keyframe animation of arbitrary values (positions, colors, brightness, ...). A timeline is a
+// list of keys sorted by time, the value between two keys is interpolated with the easing of the
+// first one. Keys live in plain slices, so timelines can be built from `static` tables.
+
+/// Values that can be interpolated between keyframes. `t` is in units of `easing::ONE` and may
+/// overshoot 0..=ONE for elastic easing.
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: i32) -> Self;
+}
+
+impl Interpolate for i32 {
+    fn interpolate(a: i32, b: i32, t: i32) -> i32 {
+        a + ((b - a) as i64 * t as i64 / ONE as i64) as i32
+    }
+}
+
+impl Interpolate for u8 {
+    fn interpolate(a: u8, b: u8, t: i32) -> u8 {
+        i32::interpolate(a as i32, b as i32, t).clamp(0, 255) as u8
+    }
+}
+
+impl Interpolate for RGB8 {
+    fn interpolate(a: RGB8, b: RGB8, t: i32) -> RGB8 {
+        RGB8 {
+            r: u8::interpolate(a.r, b.r, t),
+            g: u8::interpolate(a.g, b.g, t),
+            b: u8::interpolate(a.b, b.b, t),
+        }
+    }
+}
+
+impl<A: Interpolate, B: Interpolate> Interpolate for (A, B) {
+    fn interpolate(a: (A, B), b: (A, B), t: i32) -> (A, B) {
+        (A::interpolate(a.0, b.0, t), B::interpolate(a.1, b.1, t))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key<T> {
+    /// time in ms since the start of the timeline
+    pub time: u32,
+    pub value: T,
+    /// easing towards the next key
+    pub easing: Easing,
+}
+
+impl<T> Key<T> {
+    pub const fn new(time: u32, value: T) -> Self {
+        Key {
+            time,
+            value,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub const fn eased(time: u32, value: T, easing: Easing) -> Self {
+        Key {
+            time,
+            value,
+            easing,
+        }
+    }
+}
+
+/// What happens after the last key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// stay at the last value
+    Once,
+    /// start over at the first key
+    Loop,
+    /// play backwards to the first key, then forward again
+    PingPong,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline<'a, T> {
+    keys: &'a [Key<T>],
+    repeat: Repeat,
+}
+
+impl<'a, T: Interpolate> Timeline<'a, T> {
+    /// `keys` must not be empty and be sorted by time
+    pub fn new(keys: &'a [Key<T>], repeat: Repeat) -> Self {
+        assert!(!keys.is_empty());
+        Timeline { keys, repeat }
+    }
+
+    /// time of the last key
+    pub fn duration(&self) -> u32 {
+        self.keys[self.keys.len() - 1].time
+    }
+
+    /// length of one full cycle (forth and back for ping-pong)
+    pub fn period(&self) -> u32 {
+        match self.repeat {
+            Repeat::PingPong => self.duration() * 2,
+            _ => self.duration(),
+        }
+    }
+
+    /// true if a non repeating timeline has reached its last key at time `t`
+    pub fn is_finished(&self, t: u32) -> bool {
+        self.repeat == Repeat::Once && t >= self.duration()
+    }
+
+    /// value at time `t` (ms)
+    pub fn value_at(&self, t: u32) -> T {
+        let duration = self.duration();
+        let t = match self.repeat {
+            _ if duration == 0 => 0,
+            Repeat::Once => t.min(duration),
+            Repeat::Loop => t % duration,
+            Repeat::PingPong => {
+                let t = t % (2 * duration);
+                if t > duration {
+                    2 * duration - t
+                } else {
+                    t
+                }
+            }
+        };
+
+        // last key at or before t
+        let i = self.keys.iter().rposition(|k| k.time <= t).unwrap_or(0);
+        let a = &self.keys[i];
+        match self.keys.get(i + 1) {
+            Some(b) if t >= a.time => {
+                let p = progress(t - a.time, b.time - a.time);
+                T::interpolate(a.value, b.value, a.easing.apply(p))
+            }
+            _ => a.value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_timeline {
+    use super::*;
+    use crate::easing::Curve;
+
+    const KEYS: [Key<i32>; 3] = [
+        Key::new(0, 0),
+        Key::eased(100, 100, Easing::Hold),
+        Key::new(200, 0),
+    ];
+
+    #[test]
+    fn test_interpolate() {
+        let timeline = Timeline::new(&KEYS, Repeat::Once);
+        assert_eq!(timeline.value_at(0), 0);
+        assert_eq!(timeline.value_at(50), 50);
+        assert_eq!(timeline.value_at(100), 100);
+        // held until the next key
+        assert_eq!(timeline.value_at(199), 100);
+        assert_eq!(timeline.value_at(200), 0);
+        assert_eq!(timeline.value_at(1000), 0);
+        assert!(timeline.is_finished(200));
+    }
+
+    #[test]
+    fn test_repeat() {
+        let keys = [
+            Key::eased(0, 0u8, Easing::InOut(Curve::Sine)),
+            Key::new(100, 200),
+        ];
+        let looped = Timeline::new(&keys, Repeat::Loop);
+        assert_eq!(looped.value_at(150), looped.value_at(50));
+        assert_eq!(looped.value_at(100), 0);
+
+        let ping_pong = Timeline::new(&keys, Repeat::PingPong);
+        assert_eq!(ping_pong.period(), 200);
+        assert_eq!(ping_pong.value_at(100), 200);
+        assert_eq!(ping_pong.value_at(130), ping_pong.value_at(70));
+        assert_eq!(ping_pong.value_at(200), 0);
+    }
+
+    #[test]
+    fn test_color() {
+        let keys = [
+            Key::new(0, RGB8 { r: 0, g: 0, b: 0 }),
+            Key::new(
+                10,
+                RGB8 {
+                    r: 255,
+                    g: 100,
+                    b: 50,
+                },
+            ),
+        ];
+        let timeline = Timeline::new(&keys, Repeat::Once);
+        assert_eq!(
+            timeline.value_at(5),
+            RGB8 {
+                r: 127,
+                g: 50,
+                b: 25
+            }
+        );
+    }
+}